@@ -2,21 +2,233 @@
 //!
 //! Tauri commands for terminal management via IPC.
 
+use base64::Engine;
+use serde::Deserialize;
 use std::collections::HashMap;
-use tauri::{AppHandle, State};
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::ipc::JavaScriptChannelId;
+use tauri::{AppHandle, Manager, State, Webview};
 
+use crate::pty::env::{self, EnvAudit, EnvPolicy};
+use crate::pty::flow::OutputOptions;
+use crate::pty::foreground::ForegroundProcess;
+use crate::pty::history::CommandRecord;
+use crate::pty::info::TerminalSessionInfo;
+use crate::pty::limits::ResourceLimits;
+use crate::pty::manager::GroupWriteResult;
+use crate::pty::output::OutputChannel;
+use crate::pty::profiles::{self, ShellProfile};
+use crate::pty::recording::{self, RecordingInfo};
+use crate::pty::redact::RedactionOptions;
+use crate::pty::sandbox::SandboxOptions;
+use crate::pty::screen::TerminalSnapshot;
+use crate::pty::script::{ScriptStep, ScriptTranscript};
+use crate::pty::secrets;
+use crate::pty::session::{SpawnOptions, TerminalAttachPayload, DEFAULT_CLOSE_GRACE};
+use crate::pty::stats::{ResourceStats, StatsLimits};
+use crate::pty::timeouts::SessionTimeouts;
 use crate::pty::PtyState;
 
+/// Options of `spawn_terminal`, mapped to `SpawnOptions`
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SpawnTerminalOptions {
+    cwd: Option<String>,
+    env: Option<HashMap<String, String>>,
+    scrollback_bytes: Option<usize>,
+    /// Program to run instead of the user's login shell
+    command: Option<String>,
+    args: Vec<String>,
+    /// Run the command through a login or interactive shell
+    login: Option<bool>,
+    interactive: Option<bool>,
+    /// Channel that gets the output as binary frames instead of
+    /// `terminal-output` events
+    on_output: Option<JavaScriptChannelId>,
+    output: OutputOptions,
+    /// Host the session in the session daemon so it survives an app restart
+    persistent: bool,
+    labels: HashMap<String, String>,
+    shell_integration: bool,
+    /// Name of a shell profile to start instead of the login shell
+    profile: Option<String>,
+    /// Environment variables mapped to names in the secret store
+    secrets: HashMap<String, String>,
+    redaction: RedactionOptions,
+    timeouts: SessionTimeouts,
+    stats_limits: StatsLimits,
+    resource_limits: ResourceLimits,
+    sandbox: Option<SandboxOptions>,
+}
+
 /// Spawn a new terminal session
+///
+/// Runs on a blocking thread since starting a session reads config files
+/// and may start the session daemon.
 #[tauri::command]
 pub async fn spawn_terminal(
     session_id: String,
-    cwd: Option<String>,
-    env: Option<HashMap<String, String>>,
-    state: State<'_, PtyState>,
+    options: SpawnTerminalOptions,
+    webview: Webview,
+    app: AppHandle,
+) -> Result<(), String> {
+    let output_channel: Option<OutputChannel> =
+        options.on_output.map(|channel| channel.channel_on(webview));
+    tauri::async_runtime::spawn_blocking(move || {
+        let profile = match options.profile {
+            Some(_) if options.command.is_some() => {
+                return Err("A shell profile cannot be combined with a command".to_string())
+            }
+            Some(name) => Some(profiles::find(&profiles_path(&app)?, &name)?),
+            None => None,
+        };
+        let env_policy = env::load_policy(&env_policy_path(&app)?)?;
+        let secrets = secrets::resolve(&secrets_path(&app)?, options.secrets)?;
+        let options = SpawnOptions {
+            cwd: options.cwd,
+            env: options.env,
+            scrollback_bytes: options.scrollback_bytes,
+            command: options.command,
+            args: options.args,
+            login: options.login,
+            interactive: options.interactive,
+            output_channel,
+            output_options: options.output,
+            persistent: options.persistent,
+            labels: options.labels,
+            shell_integration: options.shell_integration,
+            profile,
+            env_policy,
+            secrets,
+            redaction: options.redaction,
+            timeouts: options.timeouts,
+            stats_limits: options.stats_limits,
+            resource_limits: options.resource_limits,
+            sandbox: options.sandbox,
+        };
+        app.state::<PtyState>()
+            .spawn_session(session_id, options, app.clone().into())
+    })
+    .await
+    .map_err(|e| format!("Failed to spawn terminal: {}", e))?
+}
+
+fn config_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to resolve config directory: {}", e))
+}
+
+/// Location of the shell profiles in the app config directory
+fn profiles_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(profiles::profiles_path(&config_dir(app)?))
+}
+
+/// Location of the environment policy in the app config directory
+fn env_policy_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(env::policy_path(&config_dir(app)?))
+}
+
+/// Location of the secret store in the app data directory
+fn secrets_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve data directory: {}", e))?;
+    Ok(secrets::secrets_path(&data_dir))
+}
+
+/// List the configured shell profiles
+#[tauri::command]
+pub async fn list_shell_profiles(app: AppHandle) -> Result<Vec<ShellProfile>, String> {
+    profiles::load(&profiles_path(&app)?)
+}
+
+/// Replace the configured shell profiles
+#[tauri::command]
+pub async fn save_shell_profiles(
+    profiles: Vec<ShellProfile>,
     app: AppHandle,
 ) -> Result<(), String> {
-    state.spawn_session(session_id, cwd, env, app)
+    profiles::save(&profiles_path(&app)?, &profiles)
+}
+
+/// Get the policy deciding which app environment variables sessions inherit
+#[tauri::command]
+pub async fn get_terminal_env_policy(app: AppHandle) -> Result<EnvPolicy, String> {
+    env::load_policy(&env_policy_path(&app)?)
+}
+
+/// Set the environment policy for sessions spawned from now on
+#[tauri::command]
+pub async fn set_terminal_env_policy(policy: EnvPolicy, app: AppHandle) -> Result<(), String> {
+    env::save_policy(&env_policy_path(&app)?, &policy)
+}
+
+/// List the names of the secrets in the secret store
+#[tauri::command]
+pub async fn list_terminal_secrets(app: AppHandle) -> Result<Vec<String>, String> {
+    secrets::names(&secrets_path(&app)?)
+}
+
+/// Store a secret that sessions can reference by name, asking for its
+/// value in a native dialog; returns false if the dialog was cancelled
+#[tauri::command]
+pub async fn set_terminal_secret(name: String, app: AppHandle) -> Result<bool, String> {
+    let path = secrets_path(&app)?;
+    tauri::async_runtime::spawn_blocking(move || match secrets::prompt(&name)? {
+        Some(value) => secrets::set(&path, &name, &value).map(|_| true),
+        None => Ok(false),
+    })
+    .await
+    .map_err(|e| format!("Failed to store secret: {}", e))?
+}
+
+/// Delete a secret; returns false if there was none with this name
+#[tauri::command]
+pub async fn delete_terminal_secret(name: String, app: AppHandle) -> Result<bool, String> {
+    secrets::remove(&secrets_path(&app)?, &name)
+}
+
+/// Change when a terminal session is announced idle or closed
+#[tauri::command]
+pub async fn terminal_set_timeouts(
+    session_id: String,
+    timeouts: SessionTimeouts,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state.set_session_timeouts(&session_id, timeouts)
+}
+
+/// Resource usage of a terminal session's process tree; None where it
+/// cannot be measured (outside Linux) or once the process is gone
+#[tauri::command]
+pub async fn terminal_stats(
+    session_id: String,
+    state: State<'_, PtyState>,
+) -> Result<Option<ResourceStats>, String> {
+    state.session_stats(&session_id)
+}
+
+/// Change the usage above which a terminal session is warned about
+#[tauri::command]
+pub async fn terminal_set_stats_limits(
+    session_id: String,
+    limits: StatsLimits,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state.set_session_stats_limits(&session_id, limits)
+}
+
+/// Which variables were withheld, set and injected from the secret store
+/// when a terminal session was spawned
+#[tauri::command]
+pub async fn terminal_env_audit(
+    session_id: String,
+    state: State<'_, PtyState>,
+) -> Result<EnvAudit, String> {
+    state.env_audit(&session_id)
 }
 
 /// Write input data to a terminal session
@@ -25,6 +237,48 @@ pub async fn terminal_write(session_id: String, data: String, state: State<'_, P
     state.write_to_session(&session_id, data.as_bytes())
 }
 
+/// Raw terminal input: a byte array or a base64-encoded string
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum TerminalInput {
+    Bytes(Vec<u8>),
+    Base64(String),
+}
+
+impl TerminalInput {
+    fn into_bytes(self) -> Result<Vec<u8>, String> {
+        match self {
+            TerminalInput::Bytes(bytes) => Ok(bytes),
+            TerminalInput::Base64(encoded) => base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|e| format!("Invalid base64 input: {}", e)),
+        }
+    }
+}
+
+/// Write raw bytes to a terminal session.
+///
+/// Unlike `terminal_write` the data does not have to be valid UTF-8, so
+/// pasted binary data and arbitrary control sequences can be sent.
+#[tauri::command]
+pub async fn terminal_write_bytes(
+    session_id: String,
+    data: TerminalInput,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state.write_to_session(&session_id, &data.into_bytes()?)
+}
+
+/// Acknowledge terminal output consumed up to stream offset `seq`
+#[tauri::command]
+pub async fn terminal_ack(
+    session_id: String,
+    seq: u64,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state.ack_session(&session_id, seq)
+}
+
 /// Resize a terminal session
 #[tauri::command]
 pub async fn terminal_resize(
@@ -36,10 +290,239 @@ pub async fn terminal_resize(
     state.resize_session(&session_id, cols, rows)
 }
 
-/// Close a terminal session
+/// Reattach to a terminal session, returning its scrollback and stream position
+///
+/// Passing `on_output` moves output delivery to that channel.
+#[tauri::command]
+pub async fn terminal_attach(
+    session_id: String,
+    on_output: Option<OutputChannel>,
+    state: State<'_, PtyState>,
+) -> Result<TerminalAttachPayload, String> {
+    state.attach_session(&session_id, on_output)
+}
+
+/// Capture what a terminal session currently shows on screen
+///
+/// Always includes the visible rows as text; `styled` adds per-cell colors
+/// and attributes.
 #[tauri::command]
-pub async fn terminal_close(session_id: String, state: State<'_, PtyState>) -> Result<(), String> {
-    state.close_session(&session_id)
+pub async fn terminal_snapshot(
+    session_id: String,
+    styled: Option<bool>,
+    state: State<'_, PtyState>,
+) -> Result<TerminalSnapshot, String> {
+    state.snapshot_session(&session_id, styled.unwrap_or(false))
+}
+
+/// Start recording a terminal session as asciicast v2
+///
+/// The file is written to `<project_dir>/.specflux/recordings/` and its
+/// path is returned.
+#[tauri::command]
+pub async fn terminal_start_recording(
+    session_id: String,
+    project_dir: String,
+    state: State<'_, PtyState>,
+) -> Result<String, String> {
+    let path = state.start_recording_session(&session_id, &PathBuf::from(project_dir))?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Stop recording a terminal session, returning the recording's path
+#[tauri::command]
+pub async fn terminal_stop_recording(
+    session_id: String,
+    state: State<'_, PtyState>,
+) -> Result<Option<String>, String> {
+    let path = state.stop_recording_session(&session_id)?;
+    Ok(path.map(|p| p.to_string_lossy().into_owned()))
+}
+
+/// List the terminal recordings of a project, newest first
+#[tauri::command]
+pub async fn list_terminal_recordings(project_dir: String) -> Result<Vec<RecordingInfo>, String> {
+    recording::list_recordings(&PathBuf::from(project_dir))
+        .map_err(|e| format!("Failed to list recordings: {}", e))
+}
+
+/// Close a terminal session and wait for its processes to exit
+///
+/// Runs on a blocking thread since the processes get `grace_ms` after each
+/// signal.
+#[tauri::command]
+pub async fn terminal_close(
+    session_id: String,
+    grace_ms: Option<u64>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let grace = grace_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_CLOSE_GRACE);
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<PtyState>().close_session(&session_id, grace)
+    })
+    .await
+    .map_err(|e| format!("Failed to close terminal: {}", e))?
+}
+
+/// Get the process running in the foreground of a terminal session
+///
+/// Returns null until the session has been sampled once.
+#[tauri::command]
+pub async fn terminal_foreground_process(
+    session_id: String,
+    state: State<'_, PtyState>,
+) -> Result<Option<ForegroundProcess>, String> {
+    state.foreground_process(&session_id)
+}
+
+/// Watch a terminal session's output for a regex
+///
+/// Every match emits a `terminal-match` event with the capture groups.
+/// Matching runs on the output with escape sequences removed and may span
+/// chunks; a watcher with the same ID is replaced. `once` removes the
+/// watcher after its first match.
+#[tauri::command]
+pub async fn terminal_watch(
+    session_id: String,
+    watcher_id: String,
+    pattern: String,
+    once: Option<bool>,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state.watch_session(&session_id, watcher_id, &pattern, once.unwrap_or(false))
+}
+
+/// Remove an output watcher; returns false if it did not exist
+#[tauri::command]
+pub async fn terminal_unwatch(
+    session_id: String,
+    watcher_id: String,
+    state: State<'_, PtyState>,
+) -> Result<bool, String> {
+    state.unwatch_session(&session_id, &watcher_id)
+}
+
+/// Run an expect-style automation script against a terminal session
+///
+/// Steps (`send`, `expect`, `waitForIdle`, `waitForExit`) run in order
+/// until one fails. The transcript reports each step's timing and result,
+/// and the failed step if any. Runs on a blocking thread since scripts
+/// may wait for minutes.
+#[tauri::command]
+pub async fn terminal_run_script(
+    session_id: String,
+    steps: Vec<ScriptStep>,
+    app: AppHandle,
+) -> Result<ScriptTranscript, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<PtyState>().run_script(&session_id, steps)
+    })
+    .await
+    .map_err(|e| format!("Script runner failed: {}", e))?
+}
+
+/// List the commands run in a terminal session with their exit codes and
+/// output, oldest first
+///
+/// Needs a session spawned with `shell_integration` (or a shell that emits
+/// OSC 133 marks itself). `limit` keeps only the most recent commands.
+#[tauri::command]
+pub async fn terminal_command_history(
+    session_id: String,
+    limit: Option<usize>,
+    state: State<'_, PtyState>,
+) -> Result<Vec<CommandRecord>, String> {
+    state.command_history(&session_id, limit)
+}
+
+/// Create a group of terminal sessions that can receive the same input
+#[tauri::command]
+pub async fn terminal_group_create(
+    group_id: String,
+    session_ids: Vec<String>,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state.create_group(group_id, session_ids)
+}
+
+/// Add a terminal session to a group
+#[tauri::command]
+pub async fn terminal_group_add(
+    group_id: String,
+    session_id: String,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state.add_to_group(&group_id, session_id)
+}
+
+/// Remove a terminal session from a group; returns false if it was no member
+#[tauri::command]
+pub async fn terminal_group_remove(
+    group_id: String,
+    session_id: String,
+    state: State<'_, PtyState>,
+) -> Result<bool, String> {
+    state.remove_from_group(&group_id, &session_id)
+}
+
+/// Delete a group, leaving its sessions running
+#[tauri::command]
+pub async fn terminal_group_delete(
+    group_id: String,
+    state: State<'_, PtyState>,
+) -> Result<bool, String> {
+    Ok(state.delete_group(&group_id))
+}
+
+/// List the sessions in a group
+#[tauri::command]
+pub async fn terminal_group_members(
+    group_id: String,
+    state: State<'_, PtyState>,
+) -> Result<Vec<String>, String> {
+    state.group_members(&group_id)
+}
+
+/// Write input to every session in a group
+///
+/// Returns a result per member. Members that were closed or exited are
+/// dropped from the group.
+#[tauri::command]
+pub async fn terminal_write_group(
+    group_id: String,
+    data: String,
+    state: State<'_, PtyState>,
+) -> Result<Vec<GroupWriteResult>, String> {
+    state.write_to_group(&group_id, data.as_bytes())
+}
+
+/// Write raw bytes to every session in a group
+///
+/// Like `terminal_write_group`, but the data does not have to be valid
+/// UTF-8, as with `terminal_write_bytes`.
+#[tauri::command]
+pub async fn terminal_write_group_bytes(
+    group_id: String,
+    data: TerminalInput,
+    state: State<'_, PtyState>,
+) -> Result<Vec<GroupWriteResult>, String> {
+    state.write_to_group(&group_id, &data.into_bytes()?)
+}
+
+/// Reconnect to persistent sessions still running in the session daemon
+///
+/// Call once after launch; returns the IDs of the restored sessions, which
+/// can then be attached like any other session.
+#[tauri::command]
+pub async fn restore_terminal_sessions(app: AppHandle) -> Result<Vec<String>, String> {
+    let secrets = secrets_path(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<PtyState>().restore_sessions(&secrets, app.clone().into())
+    })
+    .await
+    .map_err(|e| format!("Failed to restore terminals: {}", e))?
 }
 
 /// List all active terminal sessions
@@ -48,6 +531,15 @@ pub async fn list_terminal_sessions(state: State<'_, PtyState>) -> Result<Vec<St
     Ok(state.list_sessions())
 }
 
+/// Describe terminal sessions, optionally only those carrying all `labels`
+#[tauri::command]
+pub async fn get_terminal_sessions(
+    labels: Option<HashMap<String, String>>,
+    state: State<'_, PtyState>,
+) -> Result<Vec<TerminalSessionInfo>, String> {
+    Ok(state.session_infos(&labels.unwrap_or_default()))
+}
+
 /// Check if a terminal session exists
 #[tauri::command]
 pub async fn has_terminal_session(session_id: String, state: State<'_, PtyState>) -> Result<bool, String> {
@@ -18,6 +18,47 @@ async fn open_url(app: tauri::AppHandle, url: String) -> Result<(), String> {
         .map_err(|e| e.to_string())
 }
 
+/// Run the PTY session daemon instead of the app when the binary was
+/// started as one. Returns false when the app should start normally.
+pub fn run_pty_daemon_if_requested() -> bool {
+    #[cfg(unix)]
+    {
+        pty::daemon::run_if_requested()
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+/// Run the launcher of a sandboxed terminal instead of the app when the
+/// binary was started as one. Returns false when the app should start
+/// normally.
+pub fn run_pty_sandbox_if_requested() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        pty::sandbox::run_if_requested()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        false
+    }
+}
+
+/// Run the launcher applying a terminal's resource limits instead of the
+/// app when the binary was started as one. Returns false when the app
+/// should start normally.
+pub fn run_pty_limits_if_requested() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        pty::limits::run_if_requested()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        false
+    }
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -81,10 +122,42 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             open_url,
             spawn_terminal,
+            list_shell_profiles,
+            save_shell_profiles,
+            get_terminal_env_policy,
+            set_terminal_env_policy,
+            list_terminal_secrets,
+            set_terminal_secret,
+            delete_terminal_secret,
             terminal_write,
+            terminal_write_bytes,
+            terminal_write_group,
+            terminal_write_group_bytes,
+            terminal_ack,
             terminal_resize,
+            terminal_attach,
+            terminal_snapshot,
+            terminal_foreground_process,
+            terminal_command_history,
+            terminal_env_audit,
+            terminal_set_timeouts,
+            terminal_stats,
+            terminal_set_stats_limits,
+            terminal_watch,
+            terminal_unwatch,
+            terminal_run_script,
+            terminal_start_recording,
+            terminal_stop_recording,
+            list_terminal_recordings,
             terminal_close,
+            terminal_group_create,
+            terminal_group_add,
+            terminal_group_remove,
+            terminal_group_delete,
+            terminal_group_members,
+            restore_terminal_sessions,
             list_terminal_sessions,
+            get_terminal_sessions,
             has_terminal_session,
             git_clone_repo,
             git_add_files,
@@ -2,5 +2,13 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    // The same binary doubles as the daemon hosting persistent terminals
+    // and as the launcher of sandboxed and resource-limited ones
+    if specflux_lib::run_pty_daemon_if_requested()
+        || specflux_lib::run_pty_sandbox_if_requested()
+        || specflux_lib::run_pty_limits_if_requested()
+    {
+        return;
+    }
     specflux_lib::run()
 }
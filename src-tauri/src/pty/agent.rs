@@ -0,0 +1,225 @@
+//! Agent State Detection
+//!
+//! Classifies what a Claude Code agent running in a session is doing, from
+//! the text on its headless screen and markers in its output, so the board
+//! can flag tasks where the agent is blocked on a human.
+
+use serde::Serialize;
+
+use super::ansi::AnsiStripper;
+use super::foreground::ForegroundProcess;
+
+/// Output showing the agent is running in the session
+const AGENT_MARKERS: [&str; 3] = ["claude code", "esc to interrupt", "? for shortcuts"];
+
+/// Screen text of a tool permission prompt
+const APPROVAL_MARKERS: [&str; 5] = [
+    "do you want to proceed?",
+    "do you want to make this edit",
+    "do you want to create",
+    "do you want to allow",
+    "no, and tell claude what to do differently",
+];
+
+/// Screen text of the status line shown while the agent works
+const THINKING_MARKERS: [&str; 1] = ["esc to interrupt"];
+
+/// What the agent in a session is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AgentState {
+    /// No agent has shown up in the session
+    Idle,
+    Thinking,
+    /// A tool call waits for the user's permission
+    AwaitingApproval,
+    /// The agent waits for the next prompt
+    AwaitingInput,
+    /// The agent exited
+    Finished,
+}
+
+/// Follows the agent state of one session
+pub struct AgentDetector {
+    state: AgentState,
+    stripper: AnsiStripper,
+    /// Lowercased end of the plain output, for markers split across chunks
+    tail: String,
+    /// An agent marker appeared in the output since the last update
+    announced: bool,
+    seen: bool,
+    /// Foreground process that announced itself as the agent
+    agent_pid: Option<u32>,
+}
+
+impl Default for AgentDetector {
+    fn default() -> Self {
+        Self {
+            state: AgentState::Idle,
+            stripper: AnsiStripper::default(),
+            tail: String::new(),
+            announced: false,
+            seen: false,
+            agent_pid: None,
+        }
+    }
+}
+
+impl AgentDetector {
+    /// Current state
+    pub fn state(&self) -> AgentState {
+        self.state
+    }
+
+    /// Look for agent markers in a chunk of output
+    pub fn observe(&mut self, data: &[u8]) {
+        let mut text = Vec::new();
+        self.stripper.feed(data, &mut text);
+        self.tail
+            .push_str(&String::from_utf8_lossy(&text).to_lowercase());
+        if AGENT_MARKERS
+            .iter()
+            .any(|marker| self.tail.contains(marker))
+        {
+            self.announced = true;
+        }
+
+        let keep = AGENT_MARKERS
+            .iter()
+            .map(|marker| marker.len())
+            .max()
+            .unwrap_or(0);
+        let mut cut = self.tail.len().saturating_sub(keep);
+        while !self.tail.is_char_boundary(cut) {
+            cut += 1;
+        }
+        self.tail.drain(..cut);
+    }
+
+    /// Re-evaluate the state from the visible screen text and the current
+    /// foreground process; returns the new state if it changed
+    pub fn update(
+        &mut self,
+        screen: &str,
+        foreground: Option<&ForegroundProcess>,
+        exited: bool,
+    ) -> Option<AgentState> {
+        if std::mem::take(&mut self.announced) {
+            self.seen = true;
+            // A shell leader only counts before an agent was found, so a
+            // new agent started from the shell replaces a finished one
+            if let Some(process) = foreground {
+                if self.agent_pid != Some(process.pid)
+                    && (self.agent_pid.is_none() || !process.is_leader)
+                {
+                    self.agent_pid = Some(process.pid);
+                }
+            }
+        }
+
+        let returned_to_shell = matches!(
+            (self.agent_pid, foreground),
+            (Some(pid), Some(process)) if process.pid != pid && process.is_leader
+        );
+        let state = if !self.seen {
+            AgentState::Idle
+        } else if exited || returned_to_shell {
+            AgentState::Finished
+        } else {
+            classify(screen)
+        };
+
+        if state == self.state {
+            return None;
+        }
+        self.state = state;
+        Some(state)
+    }
+}
+
+/// State of a running agent judging by its screen
+fn classify(screen: &str) -> AgentState {
+    let screen = screen.to_lowercase();
+    let shows = |markers: &[&str]| markers.iter().any(|marker| screen.contains(marker));
+    if shows(&APPROVAL_MARKERS) {
+        AgentState::AwaitingApproval
+    } else if shows(&THINKING_MARKERS) {
+        AgentState::Thinking
+    } else {
+        AgentState::AwaitingInput
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THINKING: &str =
+        "> fix the tests\n\n✻ Pondering… (12s · ↑ 1.2k tokens · esc to interrupt)\n";
+    const APPROVAL: &str = " Bash command\n   npm test\n Do you want to proceed?\n ❯ 1. Yes\n   2. No, and tell Claude what to do differently (esc)\n";
+    const INPUT: &str = "● All 42 tests pass.\n\n╭──────╮\n│ >    │\n╰──────╯\n  ? for shortcuts\n";
+
+    fn process(pid: u32, is_leader: bool) -> ForegroundProcess {
+        ForegroundProcess {
+            pid,
+            name: "claude".into(),
+            is_leader,
+        }
+    }
+
+    #[test]
+    fn test_classify_screens() {
+        assert_eq!(classify(THINKING), AgentState::Thinking);
+        assert_eq!(classify(APPROVAL), AgentState::AwaitingApproval);
+        assert_eq!(classify(INPUT), AgentState::AwaitingInput);
+    }
+
+    #[test]
+    fn test_agent_lifecycle_in_shell() {
+        let shell = process(100, true);
+        let agent = process(200, false);
+        let mut detector = AgentDetector::default();
+
+        assert_eq!(detector.update("$ ", Some(&shell), false), None);
+        assert_eq!(detector.state(), AgentState::Idle);
+
+        detector.observe(b"\x1b[1m\xe2\x9c\xbb Welcome to Claude\x1b[0m C");
+        detector.observe(b"ode!\r\n");
+        assert_eq!(
+            detector.update(INPUT, Some(&agent), false),
+            Some(AgentState::AwaitingInput)
+        );
+        assert_eq!(
+            detector.update(THINKING, Some(&agent), false),
+            Some(AgentState::Thinking)
+        );
+        assert_eq!(
+            detector.update(APPROVAL, Some(&agent), false),
+            Some(AgentState::AwaitingApproval)
+        );
+
+        // The last screen lingers after the agent exits back to the shell
+        assert_eq!(
+            detector.update(APPROVAL, Some(&shell), false),
+            Some(AgentState::Finished)
+        );
+        assert_eq!(detector.update(THINKING, Some(&shell), false), None);
+    }
+
+    #[test]
+    fn test_agent_as_session_process_finishes_on_exit() {
+        let agent = process(300, true);
+        let mut detector = AgentDetector::default();
+
+        detector.observe(b"esc to interrupt");
+        detector.update(THINKING, Some(&agent), false);
+        assert_eq!(
+            detector.update(INPUT, Some(&agent), false),
+            Some(AgentState::AwaitingInput)
+        );
+        assert_eq!(
+            detector.update(INPUT, None, true),
+            Some(AgentState::Finished)
+        );
+    }
+}
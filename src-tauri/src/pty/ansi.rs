@@ -0,0 +1,106 @@
+//! Plain Text Extraction
+//!
+//! Strips escape sequences and control characters from terminal output so
+//! it can be stored or searched as text. Sequences may be split across
+//! chunks.
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+const BACKSPACE: u8 = 0x08;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    /// `ESC [` up to a final byte
+    Csi,
+    /// OSC, DCS, SOS, PM or APC up to BEL or `ESC \`
+    String,
+    StringEscape,
+}
+
+/// Incremental escape sequence stripper
+pub struct AnsiStripper {
+    state: State,
+}
+
+impl Default for AnsiStripper {
+    fn default() -> Self {
+        Self {
+            state: State::Ground,
+        }
+    }
+}
+
+impl AnsiStripper {
+    /// Append the printable text of `data` to `text`.
+    ///
+    /// Carriage returns are dropped and backspaces erase the previous
+    /// byte, which is close enough for line-oriented output.
+    pub fn feed(&mut self, data: &[u8], text: &mut Vec<u8>) {
+        for &byte in data {
+            self.state = match (self.state, byte) {
+                (State::Ground, ESC) => State::Escape,
+                (State::Ground, b'\n' | b'\t') => {
+                    text.push(byte);
+                    State::Ground
+                }
+                (State::Ground, BACKSPACE) => {
+                    if text.last().is_some_and(|&last| last != b'\n') {
+                        text.pop();
+                    }
+                    State::Ground
+                }
+                (State::Ground, 0..=0x1f | 0x7f) => State::Ground,
+                (State::Ground, _) => {
+                    text.push(byte);
+                    State::Ground
+                }
+                (State::Escape, b'[') => State::Csi,
+                (State::Escape, b']' | b'P' | b'X' | b'^' | b'_') => State::String,
+                (State::Escape, ESC) => State::Escape,
+                // Two-byte sequences, plus charset designations whose
+                // argument byte is printable and simply gets dropped here
+                (State::Escape, _) => State::Ground,
+                (State::Csi, 0x40..=0x7e) => State::Ground,
+                (State::Csi, ESC) => State::Escape,
+                (State::Csi, _) => State::Csi,
+                (State::String, BEL) => State::Ground,
+                (State::String, ESC) => State::StringEscape,
+                (State::String, _) => State::String,
+                (State::StringEscape, b'\\') => State::Ground,
+                (State::StringEscape, ESC) => State::Escape,
+                (State::StringEscape, _) => State::String,
+            };
+        }
+    }
+}
+
+/// Printable text of a complete piece of output
+pub fn strip(data: &[u8]) -> String {
+    let mut text = Vec::with_capacity(data.len());
+    AnsiStripper::default().feed(data, &mut text);
+    String::from_utf8_lossy(&text).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_removes_sequences_and_controls() {
+        let output =
+            b"\x1b[1;32mok\x1b[0m 3 tests\r\n\x1b]0;title\x07done\x1b]133;D;0\x1b\\ab\x08c\n";
+        assert_eq!(strip(output), "ok 3 tests\ndoneac\n");
+    }
+
+    #[test]
+    fn test_stripper_handles_split_sequences() {
+        let mut stripper = AnsiStripper::default();
+        let mut text = Vec::new();
+        stripper.feed(b"a\x1b[3", &mut text);
+        stripper.feed(b"1mb\x1b]7;file:", &mut text);
+        stripper.feed(b"///tmp\x07c", &mut text);
+        assert_eq!(text, b"abc");
+    }
+}
@@ -0,0 +1,162 @@
+//! PTY Child Process
+//!
+//! Waits on the process spawned into a PTY, reaps it and decodes how it
+//! terminated, and tears down the process tree when a session closes.
+
+use portable_pty::Child;
+use std::time::{Duration, Instant};
+
+/// How often teardown checks whether the process tree is gone
+const TERMINATE_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How the child process of a session terminated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChildExit {
+    /// Exit code if the process exited normally
+    pub exit_code: Option<i32>,
+    /// Signal number if the process was terminated by a signal
+    pub signal: Option<i32>,
+}
+
+/// Block until the child exits and reap it
+pub fn wait(child: &mut Box<dyn Child + Send + Sync>) -> ChildExit {
+    // portable-pty only exposes the signal name, so wait on the pid
+    // directly to get the raw status
+    #[cfg(unix)]
+    if let Some(pid) = child.process_id() {
+        return wait_pid(pid as libc::pid_t);
+    }
+
+    match child.wait() {
+        Ok(status) => ChildExit {
+            exit_code: Some(status.exit_code() as i32),
+            signal: None,
+        },
+        Err(_) => ChildExit::default(),
+    }
+}
+
+#[cfg(unix)]
+fn wait_pid(pid: libc::pid_t) -> ChildExit {
+    let mut status: libc::c_int = 0;
+    loop {
+        let rc = unsafe { libc::waitpid(pid, &mut status, 0) };
+        if rc == pid {
+            return decode_status(status);
+        }
+        if rc == -1 && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+            continue;
+        }
+        return ChildExit::default();
+    }
+}
+
+#[cfg(unix)]
+fn decode_status(status: libc::c_int) -> ChildExit {
+    if libc::WIFEXITED(status) {
+        ChildExit {
+            exit_code: Some(libc::WEXITSTATUS(status)),
+            signal: None,
+        }
+    } else if libc::WIFSIGNALED(status) {
+        ChildExit {
+            exit_code: None,
+            signal: Some(libc::WTERMSIG(status)),
+        }
+    } else {
+        ChildExit::default()
+    }
+}
+
+/// Terminate every process in the PTY session led by `pid`.
+///
+/// Sends SIGHUP, then SIGTERM, then SIGKILL to each process group in the
+/// session, waiting up to `grace` after each signal for the tree to exit.
+/// Returns true once no process of the session remains.
+#[cfg(unix)]
+pub fn terminate_tree(pid: u32, foreground_pgrp: Option<libc::pid_t>, grace: Duration) -> bool {
+    let session = pid as libc::pid_t;
+
+    for signal in [libc::SIGHUP, libc::SIGTERM, libc::SIGKILL] {
+        let groups = live_process_groups(session, foreground_pgrp);
+        if groups.is_empty() {
+            return true;
+        }
+        for pgrp in groups {
+            unsafe { libc::killpg(pgrp, signal) };
+        }
+
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline {
+            if live_process_groups(session, foreground_pgrp).is_empty() {
+                return true;
+            }
+            std::thread::sleep(TERMINATE_POLL_INTERVAL);
+        }
+    }
+
+    live_process_groups(session, foreground_pgrp).is_empty()
+}
+
+/// Process groups of the session that still contain a running process.
+///
+/// Job-control shells put every job in its own group, so signalling only
+/// the leader's group would leave those jobs running.
+#[cfg(target_os = "linux")]
+fn live_process_groups(
+    session: libc::pid_t,
+    _foreground_pgrp: Option<libc::pid_t>,
+) -> Vec<libc::pid_t> {
+    let mut groups: Vec<libc::pid_t> = super::procfs::session_processes(session)
+        .into_iter()
+        .map(|stat| stat.pgrp)
+        .collect();
+    groups.sort_unstable();
+    groups.dedup();
+    groups
+}
+
+/// Without /proc only the leader's and the foreground group are known
+#[cfg(all(unix, not(target_os = "linux")))]
+fn live_process_groups(
+    session: libc::pid_t,
+    foreground_pgrp: Option<libc::pid_t>,
+) -> Vec<libc::pid_t> {
+    let mut groups = vec![session];
+    if let Some(pgrp) = foreground_pgrp {
+        if pgrp != session {
+            groups.push(pgrp);
+        }
+    }
+    groups.retain(|&pgrp| unsafe { libc::killpg(pgrp, 0) } == 0);
+    groups
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+
+    fn run_in_pty(script: &str) -> ChildExit {
+        let pair = native_pty_system().openpty(PtySize::default()).unwrap();
+        let mut cmd = CommandBuilder::new("/bin/sh");
+        cmd.arg("-c");
+        cmd.arg(script);
+        let mut child = pair.slave.spawn_command(cmd).unwrap();
+        wait(&mut child)
+    }
+
+    #[test]
+    fn test_wait_reports_exit_code() {
+        let exit = run_in_pty("exit 3");
+        assert_eq!(exit.exit_code, Some(3));
+        assert_eq!(exit.signal, None);
+    }
+
+    #[test]
+    fn test_wait_reports_terminating_signal() {
+        let exit = run_in_pty("kill -TERM $$");
+        assert_eq!(exit.exit_code, None);
+        assert_eq!(exit.signal, Some(libc::SIGTERM));
+    }
+}
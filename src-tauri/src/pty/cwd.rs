@@ -0,0 +1,105 @@
+//! Working Directory Tracking
+//!
+//! Follows the current directory of a session after the user or agent runs
+//! `cd`: from OSC 7 reports when the shell sends them, otherwise from the
+//! working directory of the foreground process in `/proc`.
+
+use super::osc;
+
+/// Current directory of a session
+pub struct CwdTracker {
+    pid: Option<u32>,
+    current: Option<String>,
+    /// The shell reports its directory itself, so /proc is not consulted
+    reported_by_shell: bool,
+}
+
+impl CwdTracker {
+    /// Track the session led by `pid`, starting in `initial`
+    pub fn new(pid: Option<u32>, initial: Option<String>) -> Self {
+        Self {
+            pid,
+            current: initial,
+            reported_by_shell: false,
+        }
+    }
+
+    /// Last known directory
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    /// Apply the data of an OSC 7 sequence; returns the directory if it changed
+    pub fn report(&mut self, url: &str) -> Option<String> {
+        let path = osc::file_url_path(url)?;
+        self.reported_by_shell = true;
+        self.set(path)
+    }
+
+    /// Look up the foreground process's directory unless the shell reports
+    /// it; returns the directory if it changed
+    pub fn poll(&mut self) -> Option<String> {
+        if self.reported_by_shell {
+            return None;
+        }
+        let path = self.foreground_cwd()?;
+        self.set(path)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn foreground_cwd(&self) -> Option<String> {
+        use super::procfs;
+
+        let pid = self.pid? as i32;
+        let foreground = procfs::read_stat(pid)
+            .map(|stat| stat.tpgid)
+            .filter(|&tpgid| tpgid > 0);
+        foreground
+            .and_then(procfs::cwd)
+            .or_else(|| procfs::cwd(pid))
+            .map(|path| path.to_string_lossy().into_owned())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn foreground_cwd(&self) -> Option<String> {
+        let _ = self.pid;
+        None
+    }
+
+    fn set(&mut self, path: String) -> Option<String> {
+        if self.current.as_deref() == Some(path.as_str()) {
+            return None;
+        }
+        self.current = Some(path.clone());
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_overrides_and_disables_polling() {
+        let mut tracker = CwdTracker::new(Some(std::process::id()), Some("/start".into()));
+        assert_eq!(
+            tracker.report("file://host/work/repo").as_deref(),
+            Some("/work/repo")
+        );
+        assert_eq!(tracker.report("file://host/work/repo"), None);
+        assert_eq!(tracker.poll(), None);
+        assert_eq!(tracker.current(), Some("/work/repo"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_poll_reads_process_cwd() {
+        let mut tracker = CwdTracker::new(Some(std::process::id()), None);
+        let expected = std::env::current_dir().unwrap();
+        assert_eq!(
+            tracker.poll(),
+            Some(expected.to_string_lossy().into_owned())
+        );
+        assert_eq!(tracker.poll(), None);
+    }
+}
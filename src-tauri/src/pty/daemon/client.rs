@@ -0,0 +1,336 @@
+//! Session Daemon Client
+//!
+//! App side of a connection to a daemon-hosted session: a control handle
+//! for input, resize and close, and a reader yielding the session output.
+
+use parking_lot::Mutex;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::protocol::{
+    self, HostedSessionInfo, Reply, Request, SpawnRequest, FRAME_CONTROL, FRAME_DATA,
+};
+use crate::pty::child::ChildExit;
+use crate::pty::timeouts::SessionTimeouts;
+
+/// How long to wait for a freshly started daemon to accept connections
+const DAEMON_START_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Control handle of an attached session
+pub struct Connection {
+    stream: Mutex<UnixStream>,
+    pid: Option<u32>,
+    cols: u16,
+    rows: u16,
+}
+
+impl Connection {
+    /// Process id of the session's child, as seen by the daemon
+    pub fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    /// PTY size when the connection attached, as (rows, cols)
+    pub fn size(&self) -> (u16, u16) {
+        (self.rows, self.cols)
+    }
+
+    /// Write input to the session
+    pub fn input(&self, data: &[u8]) -> io::Result<()> {
+        protocol::write_frame(&mut *self.stream.lock(), FRAME_DATA, data)
+    }
+
+    pub fn resize(&self, cols: u16, rows: u16) -> io::Result<()> {
+        protocol::send(&mut *self.stream.lock(), &Request::Resize { cols, rows })
+    }
+
+    /// Have the daemon keep `timeouts` for whoever attaches next
+    pub fn set_timeouts(&self, timeouts: SessionTimeouts) -> io::Result<()> {
+        protocol::send(&mut *self.stream.lock(), &Request::SetTimeouts { timeouts })
+    }
+
+    /// Ask the daemon to terminate the session; the output reader ends once
+    /// the processes are gone
+    pub fn close(&self, grace: Duration) -> io::Result<()> {
+        let grace_ms = grace.as_millis() as u64;
+        protocol::send(&mut *self.stream.lock(), &Request::Close { grace_ms })
+    }
+
+    /// Disconnect, leaving the session running in the daemon
+    pub fn detach(&self) {
+        let _ = self.stream.lock().shutdown(std::net::Shutdown::Both);
+    }
+}
+
+/// `Write` adapter sending input frames over a connection
+pub struct InputWriter(pub Arc<Connection>);
+
+impl Write for InputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.input(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `Read` adapter yielding the output of an attached session.
+///
+/// Reports EOF once the session exits (after publishing the exit status)
+/// or the connection is detached.
+pub struct OutputReader {
+    stream: UnixStream,
+    pending: Vec<u8>,
+    offset: usize,
+    exit: mpsc::Sender<ChildExit>,
+}
+
+impl Read for OutputReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset == self.pending.len() {
+            let Some((kind, payload)) = protocol::read_frame(&mut self.stream)? else {
+                return Ok(0);
+            };
+            if kind == FRAME_DATA {
+                self.pending = payload;
+                self.offset = 0;
+                continue;
+            }
+            match protocol::decode::<Reply>(&payload)? {
+                Reply::Exit { exit_code, signal } => {
+                    let _ = self.exit.send(ChildExit { exit_code, signal });
+                    return Ok(0);
+                }
+                Reply::Error { message } => eprintln!("PTY session daemon: {}", message),
+                _ => {}
+            }
+        }
+
+        let n = buf.len().min(self.pending.len() - self.offset);
+        buf[..n].copy_from_slice(&self.pending[self.offset..self.offset + n]);
+        self.offset += n;
+        Ok(n)
+    }
+}
+
+/// A connection attached to a hosted session
+pub struct Attachment {
+    pub connection: Arc<Connection>,
+    /// Output, starting with a replay of the daemon's scrollback
+    pub output: OutputReader,
+    /// Receives the exit status; disconnects if the session is detached
+    pub exit: mpsc::Receiver<ChildExit>,
+}
+
+/// Start a session in the daemon, starting the daemon if needed
+pub fn spawn(socket: &Path, request: SpawnRequest) -> Result<Attachment, String> {
+    let stream = connect_or_start(socket)
+        .map_err(|e| format!("Failed to reach PTY session daemon: {}", e))?;
+    handshake(stream, &Request::Spawn(Box::new(request)))
+}
+
+/// Attach to a session already running in the daemon
+pub fn attach(socket: &Path, session_id: &str) -> Result<Attachment, String> {
+    let stream =
+        super::connect(socket).map_err(|e| format!("Failed to reach PTY session daemon: {}", e))?;
+    let request = Request::Attach {
+        session_id: session_id.to_string(),
+    };
+    handshake(stream, &request)
+}
+
+/// List sessions hosted by the daemon; none if it is not running
+pub fn list(socket: &Path) -> Result<Vec<HostedSessionInfo>, String> {
+    let mut stream = match super::connect(socket) {
+        Ok(stream) => stream,
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            return Err(format!("Refusing PTY session daemon: {}", e))
+        }
+        Err(_) => return Ok(Vec::new()),
+    };
+    match request(&mut stream, &Request::List)
+        .map_err(|e| format!("Failed to list daemon sessions: {}", e))?
+    {
+        Reply::Sessions { sessions } => Ok(sessions),
+        Reply::Error { message } => Err(message),
+        reply => Err(format!("Unexpected reply from PTY daemon: {:?}", reply)),
+    }
+}
+
+fn handshake(mut stream: UnixStream, message: &Request) -> Result<Attachment, String> {
+    let reply =
+        request(&mut stream, message).map_err(|e| format!("PTY daemon request failed: {}", e))?;
+    let (pid, cols, rows) = match reply {
+        Reply::Attached { pid, cols, rows } => (pid, cols, rows),
+        Reply::Error { message } => return Err(message),
+        reply => return Err(format!("Unexpected reply from PTY daemon: {:?}", reply)),
+    };
+
+    let reader = stream
+        .try_clone()
+        .map_err(|e| format!("Failed to clone daemon connection: {}", e))?;
+    let (exit_tx, exit_rx) = mpsc::channel();
+    Ok(Attachment {
+        connection: Arc::new(Connection {
+            stream: Mutex::new(stream),
+            pid,
+            cols,
+            rows,
+        }),
+        output: OutputReader {
+            stream: reader,
+            pending: Vec::new(),
+            offset: 0,
+            exit: exit_tx,
+        },
+        exit: exit_rx,
+    })
+}
+
+/// Send a request and read the control reply to it
+fn request(stream: &mut UnixStream, message: &Request) -> io::Result<Reply> {
+    protocol::send(stream, message)?;
+    match protocol::read_frame(stream)? {
+        Some((FRAME_CONTROL, payload)) => protocol::decode(&payload),
+        _ => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "daemon closed the connection",
+        )),
+    }
+}
+
+fn connect_or_start(socket: &Path) -> io::Result<UnixStream> {
+    match super::connect(socket) {
+        Ok(stream) => return Ok(stream),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => return Err(e),
+        Err(_) => {}
+    }
+    super::start()?;
+
+    let deadline = Instant::now() + DAEMON_START_TIMEOUT;
+    loop {
+        match super::connect(socket) {
+            Ok(stream) => return Ok(stream),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => return Err(e),
+            Err(e) if Instant::now() >= deadline => return Err(e),
+            Err(_) => thread::sleep(Duration::from_millis(20)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::server::Daemon;
+    use super::*;
+    use crate::pty::limits::ResourceLimits;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixListener;
+
+    fn start_daemon() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "specflux_ptyd_{}_{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        super::super::create_private_dir(&dir).unwrap();
+        let socket = dir.join("pty.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        thread::spawn(move || Arc::new(Daemon::default()).serve(listener));
+        socket
+    }
+
+    fn read_until(output: &mut OutputReader, needle: &str) -> String {
+        let mut seen = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !String::from_utf8_lossy(&seen).contains(needle) {
+            let n = output.read(&mut buffer).unwrap();
+            assert!(n > 0, "EOF before {:?} in {:?}", needle, seen);
+            seen.extend_from_slice(&buffer[..n]);
+        }
+        String::from_utf8_lossy(&seen).into_owned()
+    }
+
+    #[test]
+    fn test_session_survives_detach_and_replays_scrollback() {
+        let socket = start_daemon();
+        let request = SpawnRequest {
+            session_id: "persist".into(),
+            argv: vec!["/bin/sh".into(), "-c".into(), "echo ready; exec cat".into()],
+            cwd: None,
+            env: Vec::new(),
+            clear_env: false,
+            cols: 80,
+            rows: 24,
+            scrollback_bytes: 4096,
+            labels: Default::default(),
+            redaction: Default::default(),
+            secrets: vec![("GITHUB_TOKEN".into(), "github".into())],
+            timeouts: Default::default(),
+            stats_limits: Default::default(),
+            resource_limits: ResourceLimits {
+                cpu_time_secs: Some(600),
+                ..Default::default()
+            },
+            created_at: 0,
+        };
+
+        let request_secrets = request.secrets.clone();
+        let request_limits = request.resource_limits;
+        let mut first = spawn(&socket, request).unwrap();
+        read_until(&mut first.output, "ready");
+        let timeouts = SessionTimeouts {
+            max_lifetime_ms: Some(60_000),
+            ..Default::default()
+        };
+        first.connection.set_timeouts(timeouts).unwrap();
+        first.connection.input(b"ping\n").unwrap();
+        read_until(&mut first.output, "ping");
+        first.connection.detach();
+        let mut rest = Vec::new();
+        first.output.read_to_end(&mut rest).unwrap();
+        drop(first.output);
+        assert!(first.exit.recv().is_err(), "detach reported an exit");
+
+        let listed = list(&socket).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].pid, first.connection.pid());
+        assert_eq!(listed[0].secrets, request_secrets);
+        assert_eq!(listed[0].timeouts, timeouts);
+        assert_eq!(listed[0].resource_limits, request_limits);
+
+        let mut second = attach(&socket, "persist").unwrap();
+        let replay = read_until(&mut second.output, "ping");
+        assert!(replay.contains("ready"));
+
+        second.connection.close(Duration::from_millis(200)).unwrap();
+        let mut rest = Vec::new();
+        second.output.read_to_end(&mut rest).unwrap();
+        assert!(second.exit.recv().unwrap().signal.is_some());
+
+        let _ = std::fs::remove_dir_all(socket.parent().unwrap());
+    }
+
+    #[test]
+    fn test_refuses_shared_directory() {
+        let dir = std::env::temp_dir().join(format!("specflux_ptyd_shared_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+
+        let socket = dir.join("pty.sock");
+        assert!(super::super::bind(&socket).is_err());
+        assert!(list(&socket).is_err());
+        assert!(super::super::create_private_dir(&dir).is_err());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
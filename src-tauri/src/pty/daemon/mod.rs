@@ -0,0 +1,183 @@
+//! PTY Session Daemon
+//!
+//! Persistent sessions are hosted by a small background process instead of
+//! the app, so quitting or crashing SpecFlux leaves running agents alone.
+//! The daemon is the app binary itself started with `DAEMON_ARG`; the app
+//! talks to it over a Unix domain socket and reconnects after a relaunch.
+
+pub mod client;
+pub mod protocol;
+pub mod server;
+
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Command-line flag that starts the binary as the session daemon
+pub const DAEMON_ARG: &str = "--pty-daemon";
+
+/// Per-user directory for the daemon socket and other runtime files.
+///
+/// Prefers the runtime directory so it is cleaned up on logout; callers
+/// create it with `create_private_dir`.
+pub fn runtime_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    let uid = unsafe { libc::getuid() };
+    base.join(format!("specflux-{}", uid))
+}
+
+/// Socket the daemon of the current user listens on
+pub fn socket_path() -> PathBuf {
+    runtime_dir().join("pty.sock")
+}
+
+/// Create `dir` with mode 0700, or make sure an existing one is private.
+///
+/// Without `XDG_RUNTIME_DIR` the directory lives in the shared temp
+/// directory, where another user could have created it to plant a socket
+/// or startup files of their own.
+pub fn create_private_dir(dir: &Path) -> io::Result<()> {
+    DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    check_private_dir(dir)
+}
+
+/// Fail unless `dir` is a directory of the current user that no one else
+/// can access
+fn check_private_dir(dir: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(dir)?;
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o777 != 0o700 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not private to the current user", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// Fail unless `socket` lives in a private directory and, if it exists,
+/// belongs to the current user
+fn check_socket(socket: &Path) -> io::Result<()> {
+    if let Some(dir) = socket.parent() {
+        check_private_dir(dir)?;
+    }
+    match fs::symlink_metadata(socket) {
+        Ok(metadata) if metadata.uid() != unsafe { libc::getuid() } => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} belongs to another user", socket.display()),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Fail unless the process at the other end of `stream` runs as the
+/// current user
+fn check_peer(stream: &UnixStream) -> io::Result<()> {
+    if peer_uid(stream)? != unsafe { libc::getuid() } {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "peer runs as another user",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let rc = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if rc != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let (mut uid, mut gid) = (0, 0);
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+/// Connect to the daemon on `socket`, making sure it is the current
+/// user's own
+fn connect(socket: &Path) -> io::Result<UnixStream> {
+    check_socket(socket)?;
+    let stream = UnixStream::connect(socket)?;
+    check_peer(&stream)?;
+    Ok(stream)
+}
+
+/// Run as the session daemon if the process was started with `DAEMON_ARG`.
+///
+/// Returns false when the app should start normally.
+pub fn run_if_requested() -> bool {
+    if std::env::args().nth(1).as_deref() != Some(DAEMON_ARG) {
+        return false;
+    }
+    if let Err(e) = server::run(&socket_path()) {
+        eprintln!("PTY session daemon failed: {}", e);
+    }
+    true
+}
+
+/// Listen on `socket`, replacing a stale socket left by a dead daemon
+fn bind(socket: &Path) -> io::Result<UnixListener> {
+    if let Some(dir) = socket.parent() {
+        create_private_dir(dir)?;
+    }
+    check_socket(socket)?;
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "another session daemon is running",
+            ));
+        }
+        std::fs::remove_file(socket)?;
+    }
+    UnixListener::bind(socket)
+}
+
+/// Start a detached daemon process from the current executable
+fn start() -> io::Result<()> {
+    let mut cmd = Command::new(std::env::current_exe()?);
+    cmd.arg(DAEMON_ARG)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // A new session keeps the daemon out of the app's process group and
+    // away from its controlling terminal
+    unsafe {
+        cmd.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+    let mut child = cmd.spawn()?;
+    // Reap the daemon if it exits while the app is still running
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,218 @@
+//! Session Daemon Wire Protocol
+//!
+//! Every message is a frame: a kind byte, a little-endian u32 length and
+//! the payload. Control frames carry JSON requests and replies; data frames
+//! carry raw terminal bytes (input towards the daemon, output from it).
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+use crate::pty::child::ChildExit;
+use crate::pty::limits::ResourceLimits;
+use crate::pty::redact::RedactionOptions;
+use crate::pty::stats::StatsLimits;
+use crate::pty::timeouts::SessionTimeouts;
+
+/// Frame holding a JSON `Request` or `Reply`
+pub const FRAME_CONTROL: u8 = 0;
+
+/// Frame holding raw terminal bytes
+pub const FRAME_DATA: u8 = 1;
+
+/// Largest frame either side accepts
+const MAX_FRAME_BYTES: usize = 16 * 1024 * 1024;
+
+/// Process to start in a daemon-hosted PTY
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpawnRequest {
+    pub session_id: String,
+    pub argv: Vec<String>,
+    pub cwd: Option<String>,
+    pub env: Vec<(String, String)>,
+    /// Start from an empty environment instead of the daemon's
+    #[serde(default)]
+    pub clear_env: bool,
+    pub cols: u16,
+    pub rows: u16,
+    pub scrollback_bytes: usize,
+    /// Kept by the daemon so they survive an app restart
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// How the client masks the session's output, kept to mask it again
+    /// after an app restart
+    #[serde(default)]
+    pub redaction: RedactionOptions,
+    /// `(variable, secret name)` of the variables set from the secret
+    /// store; only the names are kept, the values are looked up again
+    #[serde(default)]
+    pub secrets: Vec<(String, String)>,
+    /// Timeout policy, kept so it is still enforced after an app restart
+    #[serde(default)]
+    pub timeouts: SessionTimeouts,
+    /// Usage that triggers warnings, kept like `timeouts`
+    #[serde(default)]
+    pub stats_limits: StatsLimits,
+    /// Limits the command was started with, kept to report which one a
+    /// session restored after an app restart was killed for
+    #[serde(default)]
+    pub resource_limits: ResourceLimits,
+    /// Unix time in milliseconds
+    pub created_at: u64,
+}
+
+/// A session hosted by the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostedSessionInfo {
+    pub session_id: String,
+    pub pid: Option<u32>,
+    pub argv: Vec<String>,
+    pub cwd: Option<String>,
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub redaction: RedactionOptions,
+    #[serde(default)]
+    pub secrets: Vec<(String, String)>,
+    #[serde(default)]
+    pub timeouts: SessionTimeouts,
+    #[serde(default)]
+    pub stats_limits: StatsLimits,
+    #[serde(default)]
+    pub resource_limits: ResourceLimits,
+    pub created_at: u64,
+    /// Set once the process exited but no client has collected the status
+    pub exited: bool,
+}
+
+/// Messages sent to the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Request {
+    /// Start a session and attach this connection to it
+    Spawn(Box<SpawnRequest>),
+    /// Attach this connection to a running session, replacing any other
+    #[serde(rename_all = "camelCase")]
+    Attach { session_id: String },
+    /// List hosted sessions
+    List,
+    /// Resize the attached session
+    Resize { cols: u16, rows: u16 },
+    /// Replace the timeout policy kept for the attached session
+    SetTimeouts { timeouts: SessionTimeouts },
+    /// Terminate the attached session's process tree
+    #[serde(rename_all = "camelCase")]
+    Close { grace_ms: u64 },
+}
+
+/// Messages sent by the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Reply {
+    /// Attached; followed by a data frame replaying the scrollback
+    Attached {
+        pid: Option<u32>,
+        cols: u16,
+        rows: u16,
+    },
+    Sessions {
+        sessions: Vec<HostedSessionInfo>,
+    },
+    /// The session's process exited; the daemon forgets the session
+    #[serde(rename_all = "camelCase")]
+    Exit {
+        exit_code: Option<i32>,
+        signal: Option<i32>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl From<ChildExit> for Reply {
+    fn from(exit: ChildExit) -> Self {
+        Reply::Exit {
+            exit_code: exit.exit_code,
+            signal: exit.signal,
+        }
+    }
+}
+
+/// Write a single frame
+pub fn write_frame(writer: &mut impl Write, kind: u8, payload: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(payload.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame too large"))?;
+    let mut header = [0u8; 5];
+    header[0] = kind;
+    header[1..].copy_from_slice(&len.to_le_bytes());
+    writer.write_all(&header)?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+/// Read a single frame, or `None` once the peer hung up
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Option<(u8, Vec<u8>)>> {
+    let mut header = [0u8; 5];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    if len > MAX_FRAME_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes exceeds limit", len),
+        ));
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+    Ok(Some((header[0], payload)))
+}
+
+/// Write a control message
+pub fn send(writer: &mut impl Write, message: &impl Serialize) -> io::Result<()> {
+    let payload = serde_json::to_vec(message)?;
+    write_frame(writer, FRAME_CONTROL, &payload)
+}
+
+/// Decode the payload of a control frame
+pub fn decode<T: DeserializeOwned>(payload: &[u8]) -> io::Result<T> {
+    serde_json::from_slice(payload).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_round_trip() {
+        let mut wire = Vec::new();
+        send(
+            &mut wire,
+            &Request::Resize {
+                cols: 100,
+                rows: 30,
+            },
+        )
+        .unwrap();
+        write_frame(&mut wire, FRAME_DATA, b"\x1b[0m\xff").unwrap();
+
+        let mut reader = wire.as_slice();
+        let (kind, payload) = read_frame(&mut reader).unwrap().unwrap();
+        assert_eq!(kind, FRAME_CONTROL);
+        assert!(matches!(
+            decode::<Request>(&payload).unwrap(),
+            Request::Resize {
+                cols: 100,
+                rows: 30
+            }
+        ));
+        assert_eq!(
+            read_frame(&mut reader).unwrap(),
+            Some((FRAME_DATA, b"\x1b[0m\xff".to_vec()))
+        );
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+    }
+}
@@ -0,0 +1,406 @@
+//! Session Daemon Server
+//!
+//! Owns daemon-hosted PTYs: spawns their processes, keeps a scrollback
+//! buffer per session and relays bytes to whichever app connection is
+//! attached. Sessions keep running while no app is connected.
+
+use parking_lot::Mutex;
+use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::{self, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::protocol::{
+    self, HostedSessionInfo, Reply, Request, SpawnRequest, FRAME_CONTROL, FRAME_DATA,
+};
+use crate::pty::child::{self, ChildExit};
+use crate::pty::limits::ResourceLimits;
+use crate::pty::redact::RedactionOptions;
+use crate::pty::scrollback::Scrollback;
+use crate::pty::stats::StatsLimits;
+use crate::pty::timeouts::SessionTimeouts;
+
+/// How long the daemon stays up without any session before exiting
+const IDLE_EXIT: Duration = Duration::from_secs(60);
+
+/// How often the idle check runs
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the exit reply waits for the reader to drain remaining output
+const EXIT_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Output state of a hosted session, guarded by one lock so replayed
+/// scrollback and live output never interleave
+struct HostedOutput {
+    scrollback: Scrollback,
+    /// Attached connection and its id
+    client: Option<(u64, UnixStream)>,
+    /// Exit status not yet delivered to a client
+    exit: Option<ChildExit>,
+}
+
+impl HostedOutput {
+    /// Send a control reply to the attached client, detaching it on failure
+    fn reply(&mut self, message: &Reply) -> bool {
+        let Some((_, client)) = &mut self.client else {
+            return false;
+        };
+        if protocol::send(client, message).is_ok() {
+            return true;
+        }
+        self.client = None;
+        false
+    }
+}
+
+/// A PTY session owned by the daemon
+struct HostedSession {
+    session_id: String,
+    pid: Option<u32>,
+    argv: Vec<String>,
+    cwd: Option<String>,
+    labels: HashMap<String, String>,
+    redaction: RedactionOptions,
+    secrets: Vec<(String, String)>,
+    timeouts: Mutex<SessionTimeouts>,
+    stats_limits: StatsLimits,
+    resource_limits: ResourceLimits,
+    created_at: u64,
+    master: Mutex<Box<dyn MasterPty + Send>>,
+    writer: Mutex<Box<dyn Write + Send>>,
+    output: Mutex<HostedOutput>,
+    /// Second handle to the attached socket, so a new client can kick the
+    /// old one off even while it is blocked writing under the output lock
+    attached: Mutex<Option<UnixStream>>,
+}
+
+impl HostedSession {
+    fn info(&self) -> HostedSessionInfo {
+        HostedSessionInfo {
+            session_id: self.session_id.clone(),
+            pid: self.pid,
+            argv: self.argv.clone(),
+            cwd: self.cwd.clone(),
+            labels: self.labels.clone(),
+            redaction: self.redaction.clone(),
+            secrets: self.secrets.clone(),
+            timeouts: *self.timeouts.lock(),
+            stats_limits: self.stats_limits,
+            resource_limits: self.resource_limits,
+            created_at: self.created_at,
+            exited: self.output.lock().exit.is_some(),
+        }
+    }
+
+    /// Attach `stream` and replay the scrollback to it.
+    ///
+    /// Returns false when the session had already exited; the exit status
+    /// is delivered and the session is done.
+    fn attach(&self, client_id: u64, stream: &UnixStream) -> io::Result<bool> {
+        if let Some(previous) = self.attached.lock().take() {
+            let _ = previous.shutdown(std::net::Shutdown::Both);
+        }
+
+        let mut output = self.output.lock();
+        let size = self.master.lock().get_size().unwrap_or_default();
+        let mut client = stream.try_clone()?;
+        protocol::send(
+            &mut client,
+            &Reply::Attached {
+                pid: self.pid,
+                cols: size.cols,
+                rows: size.rows,
+            },
+        )?;
+        let (data, _) = output.scrollback.snapshot();
+        protocol::write_frame(&mut client, FRAME_DATA, &data)?;
+
+        if let Some(exit) = output.exit {
+            protocol::send(&mut client, &Reply::from(exit))?;
+            return Ok(false);
+        }
+
+        output.client = Some((client_id, client));
+        *self.attached.lock() = Some(stream.try_clone()?);
+        Ok(true)
+    }
+
+    /// Forget `client_id` if it is still the attached client
+    fn detach(&self, client_id: u64) {
+        let mut output = self.output.lock();
+        if output
+            .client
+            .as_ref()
+            .is_some_and(|(id, _)| *id == client_id)
+        {
+            output.client = None;
+            self.attached.lock().take();
+        }
+    }
+
+    fn resize(&self, cols: u16, rows: u16) -> Result<(), String> {
+        self.master
+            .lock()
+            .resize(PtySize {
+                rows,
+                cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| format!("Failed to resize PTY: {}", e))
+    }
+
+    fn terminate(&self, grace: Duration) -> Result<(), String> {
+        let Some(pid) = self.pid else {
+            return Ok(());
+        };
+        let foreground_pgrp = self.master.lock().process_group_leader();
+        if child::terminate_tree(pid, foreground_pgrp, grace) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Processes of session {} did not exit",
+                self.session_id
+            ))
+        }
+    }
+}
+
+/// Registry of hosted sessions
+#[derive(Default)]
+pub struct Daemon {
+    sessions: Mutex<HashMap<String, Arc<HostedSession>>>,
+    next_client_id: AtomicU64,
+}
+
+impl Daemon {
+    /// Accept app connections until the listener fails
+    pub fn serve(self: &Arc<Self>, listener: UnixListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            // Only the user's own app may drive their sessions
+            if super::check_peer(&stream).is_err() {
+                continue;
+            }
+            let daemon = self.clone();
+            thread::spawn(move || {
+                let _ = daemon.handle(stream);
+            });
+        }
+        Ok(())
+    }
+
+    fn handle(self: &Arc<Self>, mut stream: UnixStream) -> io::Result<()> {
+        let Some((FRAME_CONTROL, payload)) = protocol::read_frame(&mut stream)? else {
+            return Ok(());
+        };
+
+        let session = match protocol::decode::<Request>(&payload)? {
+            Request::List => {
+                let hosted: Vec<_> = self.sessions.lock().values().cloned().collect();
+                let sessions = hosted.iter().map(|s| s.info()).collect();
+                return protocol::send(&mut stream, &Reply::Sessions { sessions });
+            }
+            Request::Spawn(request) => self.spawn(*request),
+            Request::Attach { session_id } => self
+                .sessions
+                .lock()
+                .get(&session_id)
+                .cloned()
+                .ok_or_else(|| format!("Session {} not found", session_id)),
+            _ => Err("Expected a spawn, attach or list request".to_string()),
+        };
+        let session = match session {
+            Ok(session) => session,
+            Err(message) => return protocol::send(&mut stream, &Reply::Error { message }),
+        };
+
+        let client_id = self.next_client_id.fetch_add(1, Ordering::Relaxed);
+        if !session.attach(client_id, &stream)? {
+            self.sessions.lock().remove(&session.session_id);
+            return Ok(());
+        }
+
+        let result = self.relay(&session, client_id, &mut stream);
+        session.detach(client_id);
+        result
+    }
+
+    /// Forward input and control requests from an attached client
+    fn relay(
+        &self,
+        session: &HostedSession,
+        client_id: u64,
+        stream: &mut UnixStream,
+    ) -> io::Result<()> {
+        while let Some((kind, payload)) = protocol::read_frame(stream)? {
+            if kind == FRAME_DATA {
+                let mut writer = session.writer.lock();
+                writer.write_all(&payload)?;
+                writer.flush()?;
+                continue;
+            }
+
+            let result = match protocol::decode::<Request>(&payload)? {
+                Request::Resize { cols, rows } => session.resize(cols, rows),
+                Request::SetTimeouts { timeouts } => {
+                    *session.timeouts.lock() = timeouts;
+                    Ok(())
+                }
+                Request::Close { grace_ms } => session.terminate(Duration::from_millis(grace_ms)),
+                _ => Err("Unexpected request on an attached connection".to_string()),
+            };
+            if let Err(message) = result {
+                let mut output = session.output.lock();
+                if output
+                    .client
+                    .as_ref()
+                    .is_some_and(|(id, _)| *id == client_id)
+                {
+                    output.reply(&Reply::Error { message });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn spawn(self: &Arc<Self>, request: SpawnRequest) -> Result<Arc<HostedSession>, String> {
+        if self.sessions.lock().contains_key(&request.session_id) {
+            return Err(format!("Session {} already exists", request.session_id));
+        }
+
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows: request.rows,
+                cols: request.cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| format!("Failed to open PTY: {}", e))?;
+
+        let mut cmd = CommandBuilder::from_argv(request.argv.iter().map(OsString::from).collect());
+        if let Some(dir) = &request.cwd {
+            cmd.cwd(dir);
+        }
+        if request.clear_env {
+            cmd.env_clear();
+        }
+        for (key, value) in request.env {
+            cmd.env(key, value);
+        }
+
+        // Before spawning, so a failure leaves no child behind
+        let writer = pair
+            .master
+            .take_writer()
+            .map_err(|e| format!("Failed to get writer: {}", e))?;
+        let reader = pair
+            .master
+            .try_clone_reader()
+            .map_err(|e| format!("Failed to clone reader: {}", e))?;
+        let mut child = pair
+            .slave
+            .spawn_command(cmd)
+            .map_err(|e| format!("Failed to spawn process: {}", e))?;
+
+        let session = Arc::new(HostedSession {
+            session_id: request.session_id.clone(),
+            pid: child.process_id(),
+            argv: request.argv,
+            cwd: request.cwd,
+            labels: request.labels,
+            redaction: request.redaction,
+            secrets: request.secrets,
+            timeouts: Mutex::new(request.timeouts),
+            stats_limits: request.stats_limits,
+            resource_limits: request.resource_limits,
+            created_at: request.created_at,
+            master: Mutex::new(pair.master),
+            writer: Mutex::new(writer),
+            output: Mutex::new(HostedOutput {
+                scrollback: Scrollback::new(request.scrollback_bytes),
+                client: None,
+                exit: None,
+            }),
+            attached: Mutex::new(None),
+        });
+        self.sessions
+            .lock()
+            .insert(request.session_id, session.clone());
+
+        let (reader_done_tx, reader_done_rx) = mpsc::channel::<()>();
+        let reader_session = session.clone();
+        thread::spawn(move || {
+            Self::read_output(reader, &reader_session);
+            drop(reader_done_tx);
+        });
+
+        let daemon = self.clone();
+        let waiter_session = session.clone();
+        thread::spawn(move || {
+            let exit = child::wait(&mut child);
+            let _ = reader_done_rx.recv_timeout(EXIT_DRAIN_TIMEOUT);
+            let mut output = waiter_session.output.lock();
+            output.exit = Some(exit);
+            // Keep the session around until some client learns the status
+            if output.reply(&Reply::from(exit)) {
+                daemon.sessions.lock().remove(&waiter_session.session_id);
+            }
+        });
+
+        Ok(session)
+    }
+
+    /// Buffer PTY output and relay it to the attached client
+    fn read_output(mut reader: Box<dyn Read + Send>, session: &HostedSession) {
+        let mut buffer = [0u8; 4096];
+        loop {
+            let n = match reader.read(&mut buffer) {
+                Ok(0) | Err(_) => return,
+                Ok(n) => n,
+            };
+            let mut output = session.output.lock();
+            output.scrollback.push(&buffer[..n]);
+            if let Some((_, client)) = &mut output.client {
+                if protocol::write_frame(client, FRAME_DATA, &buffer[..n]).is_err() {
+                    output.client = None;
+                }
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.sessions.lock().is_empty()
+    }
+}
+
+/// Run the daemon on `socket` until it has been idle for a while
+pub fn run(socket: &Path) -> io::Result<()> {
+    let listener = super::bind(socket)?;
+    let daemon = Arc::new(Daemon::default());
+
+    let watched = daemon.clone();
+    let socket = socket.to_path_buf();
+    thread::spawn(move || {
+        let mut idle_since: Option<Instant> = None;
+        loop {
+            thread::sleep(IDLE_POLL_INTERVAL);
+            if !watched.is_empty() {
+                idle_since = None;
+                continue;
+            }
+            if idle_since.get_or_insert_with(Instant::now).elapsed() >= IDLE_EXIT {
+                let _ = std::fs::remove_file(&socket);
+                std::process::exit(0);
+            }
+        }
+    });
+
+    daemon.serve(listener)
+}
@@ -0,0 +1,234 @@
+//! Session Environment
+//!
+//! Builds the environment of a session process: the app's own variables
+//! filtered by an allow or deny list, the variables given at spawn time,
+//! and secrets resolved on the Rust side so the webview never sees them.
+//! What was removed, set and injected is kept for auditing.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File in the app config directory holding the policy
+const POLICY_FILE: &str = "terminal-env-policy.json";
+
+/// Which of the app's variables a session inherits.
+///
+/// Names may end in `*` to match a prefix, e.g. `AWS_*`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvPolicy {
+    /// Only these variables are inherited; all when unset
+    #[serde(default)]
+    pub allow: Option<Vec<String>>,
+    /// Variables never inherited, even when allowed
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl EnvPolicy {
+    /// Whether a session may inherit the app's variable `name`
+    pub fn permits(&self, name: &str) -> bool {
+        let matches = |patterns: &[String]| patterns.iter().any(|p| pattern_matches(p, name));
+        self.allow.as_deref().is_none_or(matches) && !matches(&self.deny)
+    }
+
+    /// Whether the policy lets every variable through
+    fn is_open(&self) -> bool {
+        self.allow.is_none() && self.deny.is_empty()
+    }
+}
+
+fn pattern_matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => pattern == name,
+    }
+}
+
+/// A secret from the secret store to be set as an environment variable
+#[derive(Clone)]
+pub struct SecretVar {
+    pub variable: String,
+    /// Name of the secret in the store
+    pub secret: String,
+    pub value: String,
+}
+
+/// A variable set from the secret store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InjectedSecret {
+    pub variable: String,
+    pub secret: String,
+}
+
+/// How the environment of a session was built. Holds names only, never
+/// values.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvAudit {
+    /// App variables withheld by the policy
+    pub removed: Vec<String>,
+    /// Variables from the profile, the spawn options and TERM
+    pub set: Vec<String>,
+    pub injected: Vec<InjectedSecret>,
+}
+
+/// Environment of a session process
+pub struct Environment {
+    /// Start from an empty environment instead of the app's
+    pub clear: bool,
+    pub vars: Vec<(String, String)>,
+    pub audit: EnvAudit,
+}
+
+/// Build the environment from the app's variables in `inherited`, the
+/// variables to set in order (later ones win) and the secrets, which win
+/// over everything
+pub fn build(
+    inherited: impl IntoIterator<Item = (String, String)>,
+    policy: &EnvPolicy,
+    set: Vec<(String, String)>,
+    secrets: &[SecretVar],
+) -> Environment {
+    let mut vars = Vec::new();
+    let mut removed = BTreeSet::new();
+    let clear = !policy.is_open();
+    if clear {
+        for (name, value) in inherited {
+            if policy.permits(&name) {
+                vars.push((name, value));
+            } else {
+                removed.insert(name);
+            }
+        }
+    }
+
+    let set_names: BTreeSet<String> = set.iter().map(|(name, _)| name.clone()).collect();
+    vars.extend(set);
+    vars.extend(
+        secrets
+            .iter()
+            .map(|secret| (secret.variable.clone(), secret.value.clone())),
+    );
+
+    Environment {
+        clear,
+        vars,
+        audit: EnvAudit {
+            removed: removed.into_iter().collect(),
+            set: set_names.into_iter().collect(),
+            injected: secrets
+                .iter()
+                .map(|secret| InjectedSecret {
+                    variable: secret.variable.clone(),
+                    secret: secret.secret.clone(),
+                })
+                .collect(),
+        },
+    }
+}
+
+/// Path of the policy file in `config_dir`
+pub fn policy_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(POLICY_FILE)
+}
+
+/// Read the policy; no file means every variable is inherited
+pub fn load_policy(path: &Path) -> Result<EnvPolicy, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(EnvPolicy::default()),
+        Err(e) => return Err(format!("Failed to read environment policy: {}", e)),
+    };
+    serde_json::from_str(&contents).map_err(|e| format!("Invalid environment policy: {}", e))
+}
+
+/// Store the policy used for sessions spawned from now on
+pub fn save_policy(path: &Path, policy: &EnvPolicy) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    let json = serde_json::to_string_pretty(policy)
+        .map_err(|e| format!("Failed to serialize environment policy: {}", e))?;
+    let partial = path.with_extension("json.tmp");
+    fs::write(&partial, json)
+        .and_then(|_| fs::rename(&partial, path))
+        .map_err(|e| format!("Failed to write environment policy: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(names: &[&str]) -> Vec<(String, String)> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), "x".to_string()))
+            .collect()
+    }
+
+    fn names(env: &Environment) -> Vec<&str> {
+        env.vars.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_policy_filters_inherited_variables() {
+        let inherited = vars(&["PATH", "HOME", "AWS_SECRET_ACCESS_KEY", "SPECFLUX_API_KEY"]);
+
+        let open = build(
+            inherited.clone(),
+            &EnvPolicy::default(),
+            vars(&["TERM"]),
+            &[],
+        );
+        assert!(!open.clear);
+        assert_eq!(names(&open), ["TERM"]);
+
+        let deny = EnvPolicy {
+            allow: None,
+            deny: vec!["AWS_*".into(), "SPECFLUX_API_KEY".into()],
+        };
+        let env = build(inherited.clone(), &deny, Vec::new(), &[]);
+        assert!(env.clear);
+        assert_eq!(names(&env), ["PATH", "HOME"]);
+        assert_eq!(
+            env.audit.removed,
+            ["AWS_SECRET_ACCESS_KEY", "SPECFLUX_API_KEY"]
+        );
+
+        let allow = EnvPolicy {
+            allow: Some(vec!["PATH".into(), "AWS_*".into()]),
+            deny: vec!["AWS_SECRET_*".into()],
+        };
+        assert_eq!(names(&build(inherited, &allow, Vec::new(), &[])), ["PATH"]);
+    }
+
+    #[test]
+    fn test_secrets_are_injected_last_and_audited() {
+        let secret = SecretVar {
+            variable: "SPECFLUX_API_KEY".into(),
+            secret: "specflux".into(),
+            value: "sk-123".into(),
+        };
+        let env = build(
+            Vec::new(),
+            &EnvPolicy::default(),
+            vec![("SPECFLUX_API_KEY".into(), "from-webview".into())],
+            &[secret],
+        );
+
+        assert_eq!(env.vars.last().unwrap().1, "sk-123");
+        assert_eq!(env.audit.set, ["SPECFLUX_API_KEY"]);
+        assert_eq!(
+            env.audit.injected,
+            [InjectedSecret {
+                variable: "SPECFLUX_API_KEY".into(),
+                secret: "specflux".into(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,33 @@
+//! PTY Event Emission
+//!
+//! Wraps the app handle used to emit session events so sessions can also
+//! run headless (in tests) without a Tauri app.
+
+use tauri::{AppHandle, Emitter};
+
+/// Emits session events to the frontend
+#[derive(Clone)]
+pub struct EventEmitter {
+    app: Option<AppHandle>,
+}
+
+impl EventEmitter {
+    /// Emitter that drops every event
+    #[cfg(test)]
+    pub fn discard() -> Self {
+        Self { app: None }
+    }
+
+    /// Emit an event to all windows, ignoring delivery failures
+    pub fn emit<S: serde::Serialize + Clone>(&self, event: &str, payload: S) {
+        if let Some(app) = &self.app {
+            let _ = app.emit(event, payload);
+        }
+    }
+}
+
+impl From<AppHandle> for EventEmitter {
+    fn from(app: AppHandle) -> Self {
+        Self { app: Some(app) }
+    }
+}
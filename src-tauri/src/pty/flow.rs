@@ -0,0 +1,340 @@
+//! PTY Output Flow Control
+//!
+//! Coalesces raw PTY reads into larger batches before they cross IPC, and
+//! pauses delivery (and with it the PTY reader) while the frontend has too
+//! many bytes it has not acknowledged yet.
+
+use parking_lot::{Condvar, Mutex};
+use serde::Deserialize;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use super::output::OutputStream;
+use super::utf8;
+
+/// Default time a batch may wait for more output before it is flushed
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_millis(8);
+
+/// Default batch size that triggers an immediate flush
+pub const DEFAULT_MAX_BATCH_BYTES: usize = 64 * 1024;
+
+/// How long output held back by redaction waits for the rest of a secret
+const REDACTION_HOLD: Duration = Duration::from_millis(25);
+
+/// How often a paused delivery re-checks whether it was released
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Output batching and flow-control settings for a session
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct OutputOptions {
+    /// Flush a batch this long after its first byte arrived
+    pub flush_interval_ms: Option<u64>,
+    /// Flush a batch as soon as it reaches this size
+    pub max_batch_bytes: Option<usize>,
+    /// Pause once this many delivered bytes are unacknowledged; when unset
+    /// the frontend does not need to acknowledge output at all
+    pub flow_control_window: Option<u64>,
+    /// Hold back a multi-byte UTF-8 character cut off at the end of a batch
+    /// until the rest of it arrives, so text consumers never see it split
+    pub hold_incomplete_utf8: bool,
+}
+
+impl OutputOptions {
+    fn flush_interval(&self) -> Duration {
+        self.flush_interval_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_FLUSH_INTERVAL)
+    }
+
+    fn max_batch_bytes(&self) -> usize {
+        self.max_batch_bytes.unwrap_or(DEFAULT_MAX_BATCH_BYTES)
+    }
+}
+
+struct FlowState {
+    acked_seq: u64,
+    released: bool,
+}
+
+/// Tracks how much delivered output the frontend has acknowledged
+pub struct FlowControl {
+    window: Option<u64>,
+    state: Mutex<FlowState>,
+    changed: Condvar,
+}
+
+impl FlowControl {
+    /// Create flow control with the given window, or disabled when `None`
+    pub fn new(window: Option<u64>) -> Self {
+        Self {
+            window,
+            state: Mutex::new(FlowState {
+                acked_seq: 0,
+                released: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Record that the frontend consumed output up to stream offset `seq`
+    pub fn ack(&self, seq: u64) {
+        let mut state = self.state.lock();
+        if seq > state.acked_seq {
+            state.acked_seq = seq;
+            self.changed.notify_all();
+        }
+    }
+
+    /// Stop pausing for good, e.g. because the session is closing
+    pub fn release(&self) {
+        self.state.lock().released = true;
+        self.changed.notify_all();
+    }
+
+    /// Block while more than the window is delivered but unacknowledged
+    fn wait_for_window(&self, delivered_seq: u64) {
+        let Some(window) = self.window else {
+            return;
+        };
+        let mut state = self.state.lock();
+        while !state.released && delivered_seq.saturating_sub(state.acked_seq) >= window {
+            self.changed.wait_for(&mut state, PAUSE_POLL_INTERVAL);
+        }
+    }
+}
+
+/// Deliver chunks read from the PTY in coalesced batches.
+///
+/// Runs until the reader hangs up, flushing whatever is still pending
+/// (including a held-back partial character or possible secret).
+/// While paused by flow control it stops draining `chunks`, which fills the
+/// bounded queue and in turn blocks the reader.
+pub fn pump(
+    chunks: Receiver<Vec<u8>>,
+    output: &Mutex<OutputStream>,
+    flow: &FlowControl,
+    options: &OutputOptions,
+) {
+    let flush_interval = options.flush_interval();
+    let max_batch_bytes = options.max_batch_bytes();
+
+    let mut batch: Vec<u8> = Vec::new();
+    let mut deadline: Option<Instant> = None;
+    // When output held back by redaction is released if nothing follows
+    let mut release_at: Option<Instant> = None;
+    let mut delivered_seq = 0;
+
+    loop {
+        let received = match deadline.into_iter().chain(release_at).min() {
+            Some(deadline) => {
+                chunks.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => chunks.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        let disconnected = match received {
+            Ok(chunk) => {
+                if deadline.is_none() {
+                    deadline = Some(Instant::now() + flush_interval);
+                }
+                batch.extend_from_slice(&chunk);
+                let due = batch.len() >= max_batch_bytes
+                    || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+                if !due {
+                    continue;
+                }
+                false
+            }
+            Err(RecvTimeoutError::Timeout) if batch.is_empty() => {
+                flow.wait_for_window(delivered_seq);
+                delivered_seq = output.lock().release_held();
+                release_at = None;
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+
+        // Carry an incomplete trailing character over to the next batch
+        let held = if options.hold_incomplete_utf8 && !disconnected {
+            let tail = batch.len() - utf8::incomplete_tail_len(&batch);
+            batch.split_off(tail)
+        } else {
+            Vec::new()
+        };
+
+        if !batch.is_empty() {
+            flow.wait_for_window(delivered_seq);
+            let mut output = output.lock();
+            delivered_seq = output.publish(std::mem::take(&mut batch));
+            release_at = output.is_holding().then(|| Instant::now() + REDACTION_HOLD);
+        }
+        batch = held;
+        deadline = None;
+
+        if disconnected {
+            output.lock().release_held();
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pty::events::EventEmitter;
+    use crate::pty::output::{OutputChannel, FRAME_HEADER_BYTES};
+    use crate::pty::redact::{RedactionOptions, Redactor};
+    use crate::pty::screen::Screen;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::thread;
+    use tauri::ipc::{Channel, InvokeResponseBody};
+
+    type Frames = Arc<Mutex<Vec<Vec<u8>>>>;
+
+    fn stream_with_frames() -> (Mutex<OutputStream>, Frames) {
+        let frames = Arc::new(Mutex::new(Vec::new()));
+        let sink = frames.clone();
+        let channel: OutputChannel = Channel::new(move |body| {
+            if let InvokeResponseBody::Raw(frame) = body {
+                sink.lock().push(frame[FRAME_HEADER_BYTES..].to_vec());
+            }
+            Ok(())
+        });
+        let stream = OutputStream::new(
+            "s".into(),
+            0,
+            Screen::new(24, 80),
+            Some(channel),
+            EventEmitter::discard(),
+        );
+        (Mutex::new(stream), frames)
+    }
+
+    #[test]
+    fn test_pump_coalesces_small_reads() {
+        let (output, frames) = stream_with_frames();
+        let (tx, rx) = mpsc::channel();
+        for chunk in [b"ab".to_vec(), b"cd".to_vec(), b"ef".to_vec()] {
+            tx.send(chunk).unwrap();
+        }
+        drop(tx);
+
+        let options = OutputOptions {
+            flush_interval_ms: Some(1000),
+            ..Default::default()
+        };
+        pump(rx, &output, &FlowControl::new(None), &options);
+
+        assert_eq!(*frames.lock(), vec![b"abcdef".to_vec()]);
+    }
+
+    #[test]
+    fn test_pump_flushes_at_max_batch_size() {
+        let (output, frames) = stream_with_frames();
+        let (tx, rx) = mpsc::channel();
+        for chunk in [b"abc".to_vec(), b"def".to_vec(), b"g".to_vec()] {
+            tx.send(chunk).unwrap();
+        }
+        drop(tx);
+
+        let options = OutputOptions {
+            flush_interval_ms: Some(1000),
+            max_batch_bytes: Some(4),
+            ..Default::default()
+        };
+        pump(rx, &output, &FlowControl::new(None), &options);
+
+        assert_eq!(*frames.lock(), vec![b"abcdef".to_vec(), b"g".to_vec()]);
+    }
+
+    #[test]
+    fn test_pump_holds_back_split_character() {
+        let (output, frames) = stream_with_frames();
+        let (tx, rx) = mpsc::sync_channel(0);
+        let handle = thread::spawn(move || {
+            let options = OutputOptions {
+                flush_interval_ms: Some(0),
+                hold_incomplete_utf8: true,
+                ..Default::default()
+            };
+            pump(rx, &output, &FlowControl::new(None), &options);
+        });
+
+        let euro = "\u{20AC}".as_bytes();
+        let mut first = b"price: ".to_vec();
+        first.extend_from_slice(&euro[..1]);
+        tx.send(first).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        tx.send(euro[1..].to_vec()).unwrap();
+        drop(tx);
+        handle.join().unwrap();
+
+        let frames = frames.lock();
+        assert_eq!(frames[0], b"price: ");
+        assert_eq!(frames[1], euro);
+    }
+
+    #[test]
+    fn test_pump_releases_held_token_when_quiet() {
+        let (output, frames) = stream_with_frames();
+        let options = RedactionOptions {
+            builtin: true,
+            ..Default::default()
+        };
+        let redactor = Redactor::new(&options, &[]).unwrap();
+        output.lock().set_redactor(redactor);
+        let output = Arc::new(output);
+        let (tx, rx) = mpsc::channel();
+
+        let pump_output = output.clone();
+        let handle = thread::spawn(move || {
+            let options = OutputOptions {
+                flush_interval_ms: Some(0),
+                ..Default::default()
+            };
+            pump(rx, &pump_output, &FlowControl::new(None), &options);
+        });
+
+        tx.send(b"$ gh".to_vec()).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(*frames.lock(), vec![b"$ ".to_vec(), b"gh".to_vec()]);
+
+        let token = format!("ghp_{}", "x".repeat(36));
+        tx.send(format!("\n{}", &token[..5]).into_bytes()).unwrap();
+        tx.send(token.as_bytes()[5..].to_vec()).unwrap();
+        drop(tx);
+        handle.join().unwrap();
+        assert_eq!(frames.lock()[3], "*".repeat(40).as_bytes());
+        assert_eq!(output.lock().redactions()["github-token"], 1);
+    }
+
+    #[test]
+    fn test_pump_pauses_until_acknowledged() {
+        let (output, frames) = stream_with_frames();
+        let flow = Arc::new(FlowControl::new(Some(4)));
+        let (tx, rx) = mpsc::channel();
+
+        let pump_flow = flow.clone();
+        let handle = thread::spawn(move || {
+            let options = OutputOptions {
+                flush_interval_ms: Some(0),
+                ..Default::default()
+            };
+            pump(rx, &output, &pump_flow, &options);
+        });
+
+        tx.send(b"1234".to_vec()).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        tx.send(b"5678".to_vec()).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(frames.lock().len(), 1, "second batch sent before ack");
+
+        flow.ack(4);
+        drop(tx);
+        handle.join().unwrap();
+        assert_eq!(frames.lock().len(), 2);
+    }
+}
@@ -0,0 +1,72 @@
+//! Foreground Process Detection
+//!
+//! Resolves the foreground process group of a PTY to the program it runs,
+//! telling a shell idling at its prompt apart from one running `claude`,
+//! `npm` or `cargo`.
+
+use serde::Serialize;
+
+/// The process in the foreground of a terminal
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForegroundProcess {
+    pub pid: u32,
+    /// Executable name, e.g. `zsh` or `claude`
+    pub name: String,
+    /// The session's own process (usually the shell) is in the foreground
+    pub is_leader: bool,
+}
+
+/// Describe the leader of process group `pgrp` in the session led by `leader`
+pub fn resolve(leader: u32, pgrp: i32) -> Option<ForegroundProcess> {
+    let pid = u32::try_from(pgrp).ok().filter(|&pid| pid > 0)?;
+    Some(ForegroundProcess {
+        pid,
+        name: process_name(pid)?,
+        is_leader: pid == leader,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn process_name(pid: u32) -> Option<String> {
+    let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    Some(comm.trim_end().to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn process_name(pid: u32) -> Option<String> {
+    let mut buffer = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    let len = unsafe {
+        libc::proc_pidpath(
+            pid as libc::c_int,
+            buffer.as_mut_ptr().cast(),
+            buffer.len() as u32,
+        )
+    };
+    if len <= 0 {
+        return None;
+    }
+    buffer.truncate(len as usize);
+    let path = String::from_utf8_lossy(&buffer);
+    Some(path.rsplit('/').next().unwrap_or(&path).to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn process_name(_pid: u32) -> Option<String> {
+    None
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_own_process_group() {
+        let pgrp = unsafe { libc::getpgrp() };
+        let process = resolve(pgrp as u32, pgrp).unwrap();
+        assert!(process.is_leader);
+        assert!(!process.name.is_empty());
+        assert_eq!(resolve(1, pgrp).map(|p| p.is_leader), Some(false));
+        assert_eq!(resolve(1, -1), None);
+    }
+}
@@ -0,0 +1,261 @@
+//! Command History
+//!
+//! Follows the OSC 133 marks shells with prompt integration emit (`A`
+//! prompt start, `B` input start, `C` output start, `D;<exit>` command
+//! finished) to record each command a session ran, its exit code and its
+//! output.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+use super::ansi;
+use super::info::unix_millis;
+use super::osc::{self, Osc};
+use super::scrollback::Scrollback;
+
+/// Commands kept per session
+const MAX_COMMANDS: usize = 100;
+
+/// Output kept per command; longer output keeps its tail
+const MAX_OUTPUT_BYTES: usize = 128 * 1024;
+
+/// Echoed input kept while a command line is typed
+const MAX_INPUT_BYTES: usize = 4096;
+
+/// A command run in a session
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandRecord {
+    pub command: String,
+    pub cwd: Option<String>,
+    /// Unix time in milliseconds
+    pub started_at: u64,
+    /// Unix time in milliseconds; absent while the command runs
+    pub finished_at: Option<u64>,
+    /// Absent while running, or when the shell did not report it
+    pub exit_code: Option<i32>,
+    /// Stream offset of the first output byte
+    pub output_start: u64,
+    /// Stream offset just past the output, once finished
+    pub output_end: Option<u64>,
+    /// Output as plain text
+    pub output: String,
+    /// Only the tail of the output was kept
+    pub output_truncated: bool,
+}
+
+struct Command {
+    command: String,
+    cwd: Option<String>,
+    started_at: u64,
+    finished_at: Option<u64>,
+    exit_code: Option<i32>,
+    output_start: u64,
+    output_end: Option<u64>,
+    /// Raw output, complete once finished
+    output: Vec<u8>,
+}
+
+impl Command {
+    fn record(&self, output: &[u8], output_len: u64) -> CommandRecord {
+        CommandRecord {
+            command: self.command.clone(),
+            cwd: self.cwd.clone(),
+            started_at: self.started_at,
+            finished_at: self.finished_at,
+            exit_code: self.exit_code,
+            output_start: self.output_start,
+            output_end: self.output_end,
+            output: ansi::strip(output),
+            output_truncated: (output.len() as u64) < output_len,
+        }
+    }
+}
+
+enum Phase {
+    Idle,
+    /// Collecting the echoed command line
+    Input(Vec<u8>),
+    Running(Command, Scrollback),
+}
+
+/// Commands run in a session, oldest first
+pub struct CommandHistory {
+    phase: Phase,
+    finished: VecDeque<Command>,
+}
+
+impl Default for CommandHistory {
+    fn default() -> Self {
+        Self {
+            phase: Phase::Idle,
+            finished: VecDeque::new(),
+        }
+    }
+}
+
+impl CommandHistory {
+    /// Follow a chunk of output starting at stream offset `seq`, given the
+    /// OSC sequences found in it and the session's working directory
+    pub fn feed(&mut self, seq: u64, data: &[u8], sequences: &[(usize, Osc)], cwd: Option<&str>) {
+        let mut start = 0;
+        for (end, osc) in sequences {
+            if osc.code != 133 {
+                continue;
+            }
+            self.capture(&data[start..*end]);
+            start = *end;
+            self.mark(&osc.data, seq + *end as u64, cwd);
+        }
+        self.capture(&data[start..]);
+    }
+
+    /// Commands oldest first, at most the last `limit` of them
+    pub fn records(&self, limit: Option<usize>) -> Vec<CommandRecord> {
+        let mut records: Vec<CommandRecord> = self
+            .finished
+            .iter()
+            .map(|command| {
+                let len = command.output_end.unwrap_or(command.output_start) - command.output_start;
+                command.record(&command.output, len)
+            })
+            .collect();
+        if let Phase::Running(command, output) = &self.phase {
+            let (data, len) = output.snapshot();
+            records.push(command.record(&data, len));
+        }
+        let skip = records.len().saturating_sub(limit.unwrap_or(usize::MAX));
+        records.split_off(skip)
+    }
+
+    fn capture(&mut self, data: &[u8]) {
+        match &mut self.phase {
+            Phase::Idle => {}
+            Phase::Input(input) => {
+                let room = MAX_INPUT_BYTES.saturating_sub(input.len());
+                input.extend_from_slice(&data[..data.len().min(room)]);
+            }
+            Phase::Running(_, output) => {
+                output.push(data);
+            }
+        }
+    }
+
+    /// Apply an OSC 133 mark ending at stream offset `seq`
+    fn mark(&mut self, data: &str, seq: u64, cwd: Option<&str>) {
+        let mut params = data.split(';');
+        match params.next() {
+            // A new prompt without `D` means the command's status is unknown
+            Some("A") => self.finish(seq, None),
+            Some("B") => self.phase = Phase::Input(Vec::new()),
+            Some("C") => {
+                let command = params
+                    .find_map(|param| {
+                        param
+                            .strip_prefix("cmdline_url=")
+                            .map(osc::percent_decode)
+                            .or_else(|| param.strip_prefix("cmdline=").map(str::to_string))
+                    })
+                    .or_else(|| match &self.phase {
+                        Phase::Input(input) => Some(ansi::strip(input)),
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+                self.finish(seq, None);
+                let command = Command {
+                    command: command.trim().to_string(),
+                    cwd: cwd.map(str::to_string),
+                    started_at: unix_millis(SystemTime::now()),
+                    finished_at: None,
+                    exit_code: None,
+                    output_start: seq,
+                    output_end: None,
+                    output: Vec::new(),
+                };
+                self.phase = Phase::Running(command, Scrollback::new(MAX_OUTPUT_BYTES));
+            }
+            Some("D") => {
+                let exit_code = params.next().and_then(|code| code.parse().ok());
+                self.finish(seq, exit_code);
+            }
+            _ => {}
+        }
+    }
+
+    /// Move a running command into the history
+    fn finish(&mut self, seq: u64, exit_code: Option<i32>) {
+        let Phase::Running(mut command, output) = std::mem::replace(&mut self.phase, Phase::Idle)
+        else {
+            return;
+        };
+        command.finished_at = Some(unix_millis(SystemTime::now()));
+        command.exit_code = exit_code;
+        command.output_end = Some(seq);
+        command.output = output.snapshot().0;
+
+        if self.finished.len() == MAX_COMMANDS {
+            self.finished.pop_front();
+        }
+        self.finished.push_back(command);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pty::osc::OscScanner;
+
+    fn feed_chunks(history: &mut CommandHistory, chunks: &[&[u8]]) {
+        let mut scanner = OscScanner::default();
+        let mut seq = 0;
+        for chunk in chunks {
+            let sequences = scanner.feed(chunk);
+            history.feed(seq, chunk, &sequences, Some("/repo"));
+            seq += chunk.len() as u64;
+        }
+    }
+
+    #[test]
+    fn test_records_commands_between_marks() {
+        let mut history = CommandHistory::default();
+        feed_chunks(
+            &mut history,
+            &[
+                b"\x1b]133;A\x07$ \x1b]133;B\x07",
+                b"cargo test\r\n\x1b]133;C;cmdline_url=cargo%20test\x07\x1b[32mok\x1b[0m",
+                b"\r\n\x1b]133;D;101\x07\x1b]133;A\x07$ \x1b]133;B\x07ls\r\n\x1b]13",
+                b"3;C\x07a  b\r\n",
+            ],
+        );
+
+        let records = history.records(None);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].command, "cargo test");
+        assert_eq!(records[0].cwd.as_deref(), Some("/repo"));
+        assert_eq!(records[0].exit_code, Some(101));
+        assert_eq!(records[0].output, "ok\n");
+        assert!(!records[0].output_truncated);
+
+        // Without cmdline the echoed input is used; the command still runs
+        assert_eq!(records[1].command, "ls");
+        assert_eq!(records[1].finished_at, None);
+        assert_eq!(records[1].output, "a  b\n");
+
+        assert_eq!(history.records(Some(1))[0].command, "ls");
+    }
+
+    #[test]
+    fn test_long_output_keeps_tail() {
+        let mut history = CommandHistory::default();
+        let mut output = b"\x1b]133;C;cmdline=make\x07".to_vec();
+        output.resize(output.len() + MAX_OUTPUT_BYTES, b'.');
+        output.extend_from_slice(b"FAILED\x1b]133;D;2\x07");
+        feed_chunks(&mut history, &[&output]);
+
+        let record = &history.records(None)[0];
+        assert_eq!(record.exit_code, Some(2));
+        assert!(record.output_truncated);
+        assert!(record.output.ends_with("FAILED"));
+    }
+}
@@ -0,0 +1,109 @@
+//! Terminal Session Metadata
+//!
+//! Describes what a session is running so the UI can label its tabs and
+//! find sessions by user-supplied labels such as the task they belong to.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::agent::AgentState;
+use super::foreground::ForegroundProcess;
+use super::limits::ResourceLimits;
+use super::stats::StatsLimits;
+use super::timeouts::SessionTimeouts;
+
+/// Descriptive record of a terminal session
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalSessionInfo {
+    pub session_id: String,
+    pub pid: Option<u32>,
+    /// Program and arguments the session was started with
+    pub command: Vec<String>,
+    pub cwd: Option<String>,
+    /// Unix time in milliseconds
+    pub created_at: u64,
+    /// Unix time in milliseconds of the last input or output
+    pub last_activity_at: u64,
+    pub last_input_at: u64,
+    pub last_output_at: u64,
+    pub rows: u16,
+    pub cols: u16,
+    /// Window title set by the running program
+    pub title: String,
+    /// Hosted by the session daemon
+    pub persistent: bool,
+    pub running: bool,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub labels: HashMap<String, String>,
+    /// Process in the foreground, once sampled
+    pub foreground: Option<ForegroundProcess>,
+    /// What a Claude Code agent in the session is doing
+    pub agent_state: AgentState,
+    /// Number of secrets masked in the output, by rule
+    pub redactions: BTreeMap<String, u64>,
+    pub timeouts: SessionTimeouts,
+    pub stats_limits: StatsLimits,
+    /// Limits in force; memory and CPU limits are missing without a cgroup
+    pub resource_limits: ResourceLimits,
+}
+
+impl TerminalSessionInfo {
+    /// Whether every label in `filter` is set to the same value
+    pub fn matches_labels(&self, filter: &HashMap<String, String>) -> bool {
+        filter
+            .iter()
+            .all(|(key, value)| self.labels.get(key) == Some(value))
+    }
+}
+
+/// Milliseconds since the Unix epoch
+pub fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_labels() {
+        let info = TerminalSessionInfo {
+            session_id: "s".into(),
+            pid: None,
+            command: Vec::new(),
+            cwd: None,
+            created_at: 0,
+            last_activity_at: 0,
+            last_input_at: 0,
+            last_output_at: 0,
+            rows: 24,
+            cols: 80,
+            title: String::new(),
+            persistent: false,
+            running: true,
+            exit_code: None,
+            signal: None,
+            labels: HashMap::from([
+                ("task".to_string(), "TASK-42".to_string()),
+                ("agent".to_string(), "claude".to_string()),
+            ]),
+            foreground: None,
+            agent_state: AgentState::Idle,
+            redactions: BTreeMap::new(),
+            timeouts: SessionTimeouts::default(),
+            stats_limits: StatsLimits::default(),
+            resource_limits: ResourceLimits::default(),
+        };
+
+        let filter = |key: &str, value: &str| HashMap::from([(key.into(), value.into())]);
+        assert!(info.matches_labels(&HashMap::new()));
+        assert!(info.matches_labels(&filter("task", "TASK-42")));
+        assert!(!info.matches_labels(&filter("task", "TASK-7")));
+        assert!(!info.matches_labels(&filter("repo", "x")));
+    }
+}
@@ -0,0 +1,486 @@
+//! Session Resource Limits
+//!
+//! Caps what the process tree of a session may consume so an autonomous
+//! agent cannot take the machine down with it. Where a delegated cgroup v2
+//! hierarchy is available, the session gets a cgroup of its own with
+//! memory.max, cpu.max and pids.max. The command is started through the
+//! app binary itself, run with `LIMITS_ARG`, which sets the rlimits on
+//! address space and CPU time and joins the cgroup before it execs the
+//! command, so everything the session runs is limited from the start.
+//! Linux only.
+
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::io;
+#[cfg(target_os = "linux")]
+use std::path::{Path, PathBuf};
+
+use super::child::ChildExit;
+
+/// Command-line flag that starts the binary as the limits launcher
+pub const LIMITS_ARG: &str = "--pty-limits";
+
+/// Mount point of the cgroup v2 hierarchy
+#[cfg(target_os = "linux")]
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Cgroup holding the session cgroups, created next to the app's own
+#[cfg(target_os = "linux")]
+const CGROUP_PARENT: &str = "specflux-pty";
+
+/// Period cpu.max quotas are given for, in microseconds
+#[cfg(target_os = "linux")]
+const CPU_MAX_PERIOD: u64 = 100_000;
+
+/// Limits of a session; every limit is off unless set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ResourceLimits {
+    /// Virtual memory of each process (RLIMIT_AS), in bytes
+    pub address_space_bytes: Option<u64>,
+    /// CPU time of each process (RLIMIT_CPU), in seconds
+    pub cpu_time_secs: Option<u64>,
+    /// Processes the session may run at once (pids.max); needs a cgroup
+    pub max_processes: Option<u64>,
+    /// Memory of the whole session (memory.max), in bytes; needs a cgroup
+    pub memory_max_bytes: Option<u64>,
+    /// CPU bandwidth of the whole session (cpu.max), 100 being one full
+    /// core; needs a cgroup
+    pub cpu_max_percent: Option<u64>,
+}
+
+impl ResourceLimits {
+    #[cfg(target_os = "linux")]
+    fn needs_cgroup(&self) -> bool {
+        self.memory_max_bytes.is_some()
+            || self.cpu_max_percent.is_some()
+            || self.max_processes.is_some()
+    }
+}
+
+/// What the launcher puts a session's command under
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Plan {
+    address_space_bytes: Option<u64>,
+    cpu_time_secs: Option<u64>,
+    cgroup: Option<PathBuf>,
+}
+
+/// Limit a session was killed for exceeding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExceededLimit {
+    /// A process used up its CPU time and got SIGXCPU
+    CpuTime,
+    /// The session's cgroup ran out of memory and the OOM killer ended it
+    Memory,
+}
+
+/// Limits in force for a session
+#[derive(Debug, Default)]
+pub struct SessionLimits {
+    /// What is enforced; cgroup limits are dropped when there is no cgroup
+    limits: ResourceLimits,
+    #[cfg(target_os = "linux")]
+    cgroup: Option<PathBuf>,
+}
+
+impl SessionLimits {
+    /// Set up `limits` for a session about to run `argv`, returning them
+    /// and the command line running `argv` under them.
+    ///
+    /// When no cgroup can be created the session runs without its cgroup
+    /// limits and a warning is logged.
+    #[cfg(target_os = "linux")]
+    pub fn prepare(
+        session_id: &str,
+        limits: ResourceLimits,
+        argv: Vec<String>,
+    ) -> Result<(Self, Vec<String>), String> {
+        if limits == ResourceLimits::default() {
+            return Ok((Self::default(), argv));
+        }
+        let exe = std::env::current_exe()
+            .map_err(|e| format!("Failed to locate the limits launcher: {}", e))?;
+
+        let mut enforced = limits;
+        let cgroup = if limits.needs_cgroup() {
+            match create_cgroup(session_id, &limits) {
+                Ok(cgroup) => Some(cgroup),
+                Err(e) => {
+                    eprintln!(
+                        "Session {} runs without memory, CPU and process limits: {}",
+                        session_id, e
+                    );
+                    enforced.memory_max_bytes = None;
+                    enforced.cpu_max_percent = None;
+                    enforced.max_processes = None;
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let plan = Plan {
+            address_space_bytes: limits.address_space_bytes,
+            cpu_time_secs: limits.cpu_time_secs,
+            cgroup: cgroup.clone(),
+        };
+        let plan = serde_json::to_string(&plan)
+            .map_err(|e| format!("Failed to serialize resource limits: {}", e))?;
+        let mut command = vec![
+            exe.to_string_lossy().into_owned(),
+            LIMITS_ARG.to_string(),
+            plan,
+            "--".to_string(),
+        ];
+        command.extend(argv);
+        Ok((
+            Self {
+                limits: enforced,
+                cgroup,
+            },
+            command,
+        ))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn prepare(
+        _session_id: &str,
+        limits: ResourceLimits,
+        argv: Vec<String>,
+    ) -> Result<(Self, Vec<String>), String> {
+        if limits != ResourceLimits::default() {
+            return Err("Resource limits are only supported on Linux".to_string());
+        }
+        Ok((Self::default(), argv))
+    }
+
+    /// Limits of a session restored from the session daemon, started with
+    /// `limits`; its cgroup, if it has one, is still in place
+    pub fn reattach(session_id: &str, limits: ResourceLimits) -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            let cgroup = parent_cgroup()
+                .ok()
+                .map(|parent| parent.join(cgroup_name(session_id)))
+                .filter(|cgroup| cgroup.is_dir());
+            Self { limits, cgroup }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = session_id;
+            Self { limits }
+        }
+    }
+
+    /// Limits in force
+    pub fn limits(&self) -> ResourceLimits {
+        self.limits
+    }
+
+    /// The limit the session process was killed for exceeding, if any
+    pub fn exceeded(&self, exit: ChildExit) -> Option<ExceededLimit> {
+        #[cfg(target_os = "linux")]
+        {
+            if self.limits.cpu_time_secs.is_some() && exit.signal == Some(libc::SIGXCPU) {
+                return Some(ExceededLimit::CpuTime);
+            }
+            let oom_kills = self
+                .cgroup
+                .as_ref()
+                .and_then(|cgroup| fs::read_to_string(cgroup.join("memory.events")).ok())
+                .map_or(0, |events| oom_kills(&events));
+            if exit.signal == Some(libc::SIGKILL) && oom_kills > 0 {
+                return Some(ExceededLimit::Memory);
+            }
+            None
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = exit;
+            None
+        }
+    }
+
+    /// Remove the session's cgroup once its processes are gone
+    pub fn release(&self) {
+        #[cfg(target_os = "linux")]
+        if let Some(cgroup) = &self.cgroup {
+            let _ = fs::remove_dir(cgroup);
+        }
+    }
+}
+
+/// Run as the limits launcher if the process was started with
+/// `LIMITS_ARG`: put itself under the limits and exec the command.
+///
+/// Returns false when the app should start normally.
+#[cfg(target_os = "linux")]
+pub fn run_if_requested() -> bool {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some(LIMITS_ARG) {
+        return false;
+    }
+    let error = launch(args);
+    eprintln!("Failed to apply resource limits: {}", error);
+    std::process::exit(126);
+}
+
+/// Apply the limits described by the arguments and exec the command; only
+/// returns on failure
+#[cfg(target_os = "linux")]
+fn launch(mut args: impl Iterator<Item = String>) -> String {
+    use std::os::unix::process::CommandExt;
+
+    let plan: Plan = match args.next().map(|plan| serde_json::from_str(&plan)) {
+        Some(Ok(plan)) => plan,
+        Some(Err(e)) => return format!("invalid limits: {}", e),
+        None => return "missing limits".to_string(),
+    };
+    if args.next().as_deref() != Some("--") {
+        return "missing command".to_string();
+    }
+    let argv: Vec<String> = args.collect();
+    let Some((program, args)) = argv.split_first() else {
+        return "missing command".to_string();
+    };
+
+    if let Err(e) = enter(&plan) {
+        return e.to_string();
+    }
+    std::process::Command::new(program)
+        .args(args)
+        .exec()
+        .to_string()
+}
+
+/// Move the calling process into the session's cgroup and set its rlimits,
+/// which everything it execs or forks inherits
+#[cfg(target_os = "linux")]
+fn enter(plan: &Plan) -> io::Result<()> {
+    if let Some(cgroup) = &plan.cgroup {
+        fs::write(cgroup.join("cgroup.procs"), "0")
+            .map_err(|e| io::Error::new(e.kind(), format!("cannot join cgroup: {}", e)))?;
+    }
+    let rlimits = [
+        (libc::RLIMIT_AS, plan.address_space_bytes, "address space"),
+        (libc::RLIMIT_CPU, plan.cpu_time_secs, "CPU time"),
+    ];
+    for (resource, value, name) in rlimits {
+        let Some(value) = value else {
+            continue;
+        };
+        // One second past the soft limit so SIGXCPU comes before SIGKILL
+        let hard = if resource == libc::RLIMIT_CPU {
+            value.saturating_add(1)
+        } else {
+            value
+        };
+        let limit = libc::rlimit {
+            rlim_cur: value as libc::rlim_t,
+            rlim_max: hard as libc::rlim_t,
+        };
+        if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+            let e = io::Error::last_os_error();
+            return Err(io::Error::new(
+                e.kind(),
+                format!("cannot set {} limit: {}", name, e),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Directory of the cgroup the app runs in
+#[cfg(target_os = "linux")]
+fn own_cgroup() -> Option<PathBuf> {
+    let contents = fs::read_to_string("/proc/self/cgroup").ok()?;
+    let path = unified_cgroup(&contents)?;
+    let root = Path::new(CGROUP_ROOT);
+    // Only a unified (v2) hierarchy mounted at the root will do
+    root.join("cgroup.controllers")
+        .is_file()
+        .then(|| root.join(path.trim_start_matches('/')))
+}
+
+/// Cgroup the session cgroups are created in
+#[cfg(target_os = "linux")]
+fn parent_cgroup() -> Result<PathBuf, String> {
+    let own = own_cgroup().ok_or("no cgroup v2 hierarchy")?;
+    let parent = own.parent().ok_or("the app runs in the root cgroup")?;
+    Ok(parent.join(CGROUP_PARENT))
+}
+
+/// Path of the cgroup v2 entry in the contents of `/proc/<pid>/cgroup`
+#[cfg(target_os = "linux")]
+fn unified_cgroup(contents: &str) -> Option<&str> {
+    contents.lines().find_map(|line| line.strip_prefix("0::"))
+}
+
+/// Directory name of a session's cgroup; the prefix keeps it from clashing
+/// with interface files such as `memory.max`
+#[cfg(target_os = "linux")]
+fn cgroup_name(session_id: &str) -> String {
+    let id: String = session_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("session-{}", id)
+}
+
+/// Number of processes the OOM killer ended, from `memory.events`
+#[cfg(target_os = "linux")]
+fn oom_kills(events: &str) -> u64 {
+    events
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))
+        .and_then(|count| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Create the cgroup of a session next to the app's and set its limits
+#[cfg(target_os = "linux")]
+fn create_cgroup(session_id: &str, limits: &ResourceLimits) -> Result<PathBuf, String> {
+    let parent = parent_cgroup()?;
+    if !parent.is_dir() {
+        fs::create_dir(&parent).map_err(|e| format!("cannot create cgroup: {}", e))?;
+    }
+
+    let mut controllers = Vec::new();
+    if limits.memory_max_bytes.is_some() {
+        controllers.push("+memory");
+    }
+    if limits.cpu_max_percent.is_some() {
+        controllers.push("+cpu");
+    }
+    if limits.max_processes.is_some() {
+        controllers.push("+pids");
+    }
+    fs::write(parent.join("cgroup.subtree_control"), controllers.join(" "))
+        .map_err(|e| format!("cannot enable {}: {}", controllers.join(" "), e))?;
+
+    let cgroup = parent.join(cgroup_name(session_id));
+    // A cgroup left behind by an earlier session with this id
+    let _ = fs::remove_dir(&cgroup);
+    fs::create_dir(&cgroup).map_err(|e| format!("cannot create cgroup: {}", e))?;
+
+    let setup = || -> Result<(), String> {
+        let write = |file: &str, value: String| {
+            fs::write(cgroup.join(file), value).map_err(|e| format!("cannot write {}: {}", file, e))
+        };
+        if let Some(bytes) = limits.memory_max_bytes {
+            write("memory.max", bytes.to_string())?;
+            // Without swap to fall back on, the session is killed instead
+            // of slowing the machine down; the whole session goes at once
+            let _ = write("memory.swap.max", "0".to_string());
+            let _ = write("memory.oom.group", "1".to_string());
+        }
+        if let Some(percent) = limits.cpu_max_percent {
+            let quota = (percent * CPU_MAX_PERIOD / 100).max(1000);
+            write("cpu.max", format!("{} {}", quota, CPU_MAX_PERIOD))?;
+        }
+        if let Some(processes) = limits.max_processes {
+            write("pids.max", processes.to_string())?;
+        }
+        Ok(())
+    };
+    if let Err(e) = setup() {
+        let _ = fs::remove_dir(&cgroup);
+        return Err(e);
+    }
+    Ok(cgroup)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cgroup_files() {
+        let cgroup = "12:pids:/user.slice\n0::/user.slice/user@1000.service/app.slice/x.scope\n";
+        assert_eq!(
+            unified_cgroup(cgroup),
+            Some("/user.slice/user@1000.service/app.slice/x.scope")
+        );
+        assert_eq!(unified_cgroup("4:memory:/\n"), None);
+        assert_eq!(cgroup_name("task-42/claude.1"), "session-task-42_claude_1");
+        assert_eq!(
+            oom_kills("low 0\nhigh 0\nmax 31\noom 2\noom_kill 1\noom_group_kill 1\n"),
+            1
+        );
+    }
+
+    #[test]
+    fn test_rlimits_are_set_before_exec() {
+        use std::os::unix::process::CommandExt;
+
+        let limits = ResourceLimits {
+            address_space_bytes: Some(1 << 32),
+            cpu_time_secs: Some(30),
+            ..Default::default()
+        };
+        let argv = vec!["cat".to_string(), "/proc/self/limits".to_string()];
+        let (applied, command) = SessionLimits::prepare("limits-test", limits, argv).unwrap();
+        assert_eq!(command[1], LIMITS_ARG);
+        assert_eq!(command[3..], ["--", "cat", "/proc/self/limits"]);
+        let plan: Plan = serde_json::from_str(&command[2]).unwrap();
+        assert_eq!(plan.cgroup, None);
+
+        let mut cat = std::process::Command::new("cat");
+        cat.arg("/proc/self/limits");
+        unsafe {
+            cat.pre_exec(move || enter(&plan));
+        }
+        let proc_limits = String::from_utf8(cat.output().unwrap().stdout).unwrap();
+
+        let line = |name: &str| {
+            proc_limits
+                .lines()
+                .find(|line| line.starts_with(name))
+                .unwrap()
+                .split_whitespace()
+                .rev()
+                .skip(1)
+                .take(2)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(line("Max address space"), ["4294967296", "4294967296"]);
+        assert_eq!(line("Max cpu time"), ["31", "30"]);
+        assert_eq!(applied.limits(), limits);
+        assert_eq!(
+            applied.exceeded(ChildExit {
+                exit_code: None,
+                signal: Some(libc::SIGXCPU),
+            }),
+            Some(ExceededLimit::CpuTime)
+        );
+    }
+
+    #[test]
+    fn test_reattach_keeps_limits() {
+        let limits = ResourceLimits {
+            cpu_time_secs: Some(30),
+            ..Default::default()
+        };
+        let reattached = SessionLimits::reattach("limits-reattach-test", limits);
+        assert_eq!(reattached.limits(), limits);
+        assert_eq!(
+            reattached.exceeded(ChildExit {
+                exit_code: None,
+                signal: Some(libc::SIGXCPU),
+            }),
+            Some(ExceededLimit::CpuTime)
+        );
+    }
+}
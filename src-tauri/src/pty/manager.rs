@@ -3,32 +3,115 @@
 //! Manages multiple PTY sessions with thread-safe access.
 
 use parking_lot::RwLock;
+use serde::Serialize;
 use std::collections::HashMap;
-use tauri::AppHandle;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Once, Weak};
+use std::thread;
+use std::time::Duration;
 
-use super::session::PtySession;
+use super::child::ChildExit;
+use super::env::EnvAudit;
+use super::events::EventEmitter;
+use super::foreground::ForegroundProcess;
+use super::history::CommandRecord;
+use super::info::TerminalSessionInfo;
+use super::output::OutputChannel;
+use super::screen::TerminalSnapshot;
+use super::script::{self, ScriptStep, ScriptTranscript};
+use super::session::{PtySession, SpawnOptions, TerminalAttachPayload, DEFAULT_CLOSE_GRACE};
+use super::stats::{ResourceStats, StatsLimits};
+use super::timeouts::SessionTimeouts;
+
+/// How often session timeouts are checked
+const REAP_INTERVAL: Duration = Duration::from_secs(1);
+
+type Sessions = RwLock<HashMap<String, PtySession>>;
+type Groups = RwLock<HashMap<String, Vec<String>>>;
+
+/// Outcome of writing to one member of a session group
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupWriteResult {
+    pub session_id: String,
+    pub ok: bool,
+    pub error: Option<String>,
+}
 
 /// State container for PTY sessions
-#[derive(Default)]
 pub struct PtyState {
-    sessions: RwLock<HashMap<String, PtySession>>,
+    sessions: Arc<Sessions>,
+    /// Named sets of sessions that receive the same input
+    groups: Arc<Groups>,
+    /// Starts the thread closing sessions whose timeouts expired
+    reaper: Once,
 }
 
 impl PtyState {
     /// Create a new PTY state container
     pub fn new() -> Self {
         Self {
-            sessions: RwLock::new(HashMap::new()),
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            groups: Arc::new(RwLock::new(HashMap::new())),
+            reaper: Once::new(),
+        }
+    }
+}
+
+impl Default for PtyState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PtyState {
+    /// Check session timeouts periodically from now on, for as long as
+    /// this state exists
+    fn start_reaper(&self) {
+        self.reaper.call_once(|| {
+            let sessions = Arc::downgrade(&self.sessions);
+            let groups = Arc::downgrade(&self.groups);
+            thread::spawn(move || Self::reap(sessions, groups));
+        });
+    }
+
+    fn reap(sessions: Weak<Sessions>, groups: Weak<Groups>) {
+        loop {
+            thread::sleep(REAP_INTERVAL);
+            let (Some(sessions), Some(groups)) = (sessions.upgrade(), groups.upgrade()) else {
+                return;
+            };
+            let expired: Vec<String> = sessions
+                .read()
+                .iter()
+                .filter(|(_, session)| session.check_timeouts().is_some())
+                .map(|(session_id, _)| session_id.clone())
+                .collect();
+            for session_id in expired {
+                if let Some(session) = Self::take_session(&sessions, &groups, &session_id) {
+                    if let Err(e) = session.close(DEFAULT_CLOSE_GRACE) {
+                        eprintln!("Failed to close timed out session: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remove a session from the state and from every group
+    fn take_session(sessions: &Sessions, groups: &Groups, session_id: &str) -> Option<PtySession> {
+        let session = sessions.write().remove(session_id)?;
+        for members in groups.write().values_mut() {
+            members.retain(|member| member != session_id);
         }
+        Some(session)
     }
 
     /// Spawn a new terminal session
     pub fn spawn_session(
         &self,
         session_id: String,
-        cwd: Option<String>,
-        env: Option<HashMap<String, String>>,
-        app: AppHandle,
+        options: SpawnOptions,
+        events: EventEmitter,
     ) -> Result<(), String> {
         // Check if session already exists
         {
@@ -39,17 +122,57 @@ impl PtyState {
         }
 
         // Create new session
-        let session = PtySession::spawn(session_id.clone(), cwd, env, app)?;
+        let session = PtySession::spawn(session_id.clone(), options, events)?;
 
         // Store session
         {
             let mut sessions = self.sessions.write();
             sessions.insert(session_id, session);
         }
+        self.start_reaper();
 
         Ok(())
     }
 
+    /// Reconnect to sessions left running in the session daemon.
+    ///
+    /// Returns the IDs of the sessions that were restored; their output is
+    /// delivered as events until a frontend attaches. Sessions that cannot
+    /// be reattached are logged and skipped. The values of the secrets
+    /// their output is masked for are looked up in the store at `secrets`.
+    pub fn restore_sessions(
+        &self,
+        secrets: &Path,
+        events: EventEmitter,
+    ) -> Result<Vec<String>, String> {
+        #[cfg(not(unix))]
+        {
+            let _ = (secrets, events);
+            Ok(Vec::new())
+        }
+
+        #[cfg(unix)]
+        {
+            let socket = super::daemon::socket_path();
+            let mut restored = Vec::new();
+            for hosted in super::daemon::client::list(&socket)? {
+                if self.has_session(&hosted.session_id) {
+                    continue;
+                }
+                let session_id = hosted.session_id.clone();
+                match PtySession::reattach(hosted, secrets, events.clone()) {
+                    Ok(session) => {
+                        self.sessions.write().insert(session_id.clone(), session);
+                        restored.push(session_id);
+                    }
+                    Err(e) => eprintln!("Failed to restore session {}: {}", session_id, e),
+                }
+            }
+            self.start_reaper();
+            Ok(restored)
+        }
+    }
+
     /// Write data to a terminal session
     pub fn write_to_session(&self, session_id: &str, data: &[u8]) -> Result<(), String> {
         let sessions = self.sessions.read();
@@ -59,6 +182,16 @@ impl PtyState {
         session.write(data)
     }
 
+    /// Acknowledge output a terminal session's frontend has consumed
+    pub fn ack_session(&self, session_id: &str, seq: u64) -> Result<(), String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        session.ack(seq);
+        Ok(())
+    }
+
     /// Resize a terminal session
     pub fn resize_session(&self, session_id: &str, cols: u16, rows: u16) -> Result<(), String> {
         let sessions = self.sessions.read();
@@ -68,15 +201,301 @@ impl PtyState {
         session.resize(cols, rows)
     }
 
-    /// Close and remove a terminal session
-    pub fn close_session(&self, session_id: &str) -> Result<(), String> {
-        let mut sessions = self.sessions.write();
-        if let Some(session) = sessions.remove(session_id) {
-            session.close();
-            Ok(())
-        } else {
-            Err(format!("Session {} not found", session_id))
+    /// Get buffered output of a terminal session for reattaching
+    pub fn attach_session(
+        &self,
+        session_id: &str,
+        channel: Option<OutputChannel>,
+    ) -> Result<TerminalAttachPayload, String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        Ok(session.attach(channel))
+    }
+
+    /// Capture the visible screen of a terminal session
+    pub fn snapshot_session(
+        &self,
+        session_id: &str,
+        styled: bool,
+    ) -> Result<TerminalSnapshot, String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        Ok(session.snapshot(styled))
+    }
+
+    /// Start recording a terminal session into the project's recordings
+    pub fn start_recording_session(
+        &self,
+        session_id: &str,
+        project_dir: &Path,
+    ) -> Result<PathBuf, String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        session.start_recording(project_dir)
+    }
+
+    /// Stop recording a terminal session
+    pub fn stop_recording_session(&self, session_id: &str) -> Result<Option<PathBuf>, String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        session.stop_recording()
+    }
+
+    /// Receive a copy of a terminal session's output from now on
+    pub fn tap_session(&self, session_id: &str) -> Result<mpsc::Receiver<Vec<u8>>, String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        Ok(session.tap())
+    }
+
+    /// How a terminal session's process exited, once it has
+    pub fn session_exit(&self, session_id: &str) -> Result<Option<ChildExit>, String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        Ok(session.exit_status())
+    }
+
+    /// Run an automation script against a terminal session, blocking until
+    /// it finished or a step failed
+    pub fn run_script(
+        &self,
+        session_id: &str,
+        steps: Vec<ScriptStep>,
+    ) -> Result<ScriptTranscript, String> {
+        script::run(self, session_id, steps)
+    }
+
+    /// Start matching `pattern` against a terminal session's output
+    pub fn watch_session(
+        &self,
+        session_id: &str,
+        watcher_id: String,
+        pattern: &str,
+        once: bool,
+    ) -> Result<(), String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        session.watch(watcher_id, pattern, once)
+    }
+
+    /// Remove an output watcher from a terminal session
+    pub fn unwatch_session(&self, session_id: &str, watcher_id: &str) -> Result<bool, String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        Ok(session.unwatch(watcher_id))
+    }
+
+    /// Commands run in a terminal session, at most the last `limit`
+    pub fn command_history(
+        &self,
+        session_id: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<CommandRecord>, String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        Ok(session.command_history(limit))
+    }
+
+    /// Change when a terminal session is announced idle or closed
+    pub fn set_session_timeouts(
+        &self,
+        session_id: &str,
+        timeouts: SessionTimeouts,
+    ) -> Result<(), String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        session.set_timeouts(timeouts)
+    }
+
+    /// Resource usage of a terminal session's process tree
+    pub fn session_stats(&self, session_id: &str) -> Result<Option<ResourceStats>, String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        Ok(session.stats())
+    }
+
+    /// Change the usage above which a terminal session is warned about
+    pub fn set_session_stats_limits(
+        &self,
+        session_id: &str,
+        limits: StatsLimits,
+    ) -> Result<(), String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        session.set_stats_limits(limits);
+        Ok(())
+    }
+
+    /// How the environment of a terminal session was built
+    pub fn env_audit(&self, session_id: &str) -> Result<EnvAudit, String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        Ok(session.env_audit())
+    }
+
+    /// Process currently in the foreground of a terminal session
+    pub fn foreground_process(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<ForegroundProcess>, String> {
+        let sessions = self.sessions.read();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        Ok(session.foreground())
+    }
+
+    /// Close and remove a terminal session, waiting for its processes to exit
+    pub fn close_session(&self, session_id: &str, grace: Duration) -> Result<(), String> {
+        // Release the lock before the (possibly slow) teardown
+        let session = Self::take_session(&self.sessions, &self.groups, session_id)
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+        session.close(grace)
+    }
+
+    /// Create a session group with the given members
+    pub fn create_group(&self, group_id: String, session_ids: Vec<String>) -> Result<(), String> {
+        if let Some(missing) = session_ids.iter().find(|id| !self.has_session(id)) {
+            return Err(format!("Session {} not found", missing));
+        }
+        let mut members: Vec<String> = Vec::with_capacity(session_ids.len());
+        for session_id in session_ids {
+            if !members.contains(&session_id) {
+                members.push(session_id);
+            }
+        }
+
+        let mut groups = self.groups.write();
+        if groups.contains_key(&group_id) {
+            return Err(format!("Group {} already exists", group_id));
+        }
+        groups.insert(group_id, members);
+        Ok(())
+    }
+
+    /// Add a session to a group
+    pub fn add_to_group(&self, group_id: &str, session_id: String) -> Result<(), String> {
+        if !self.has_session(&session_id) {
+            return Err(format!("Session {} not found", session_id));
+        }
+        let mut groups = self.groups.write();
+        let members = groups
+            .get_mut(group_id)
+            .ok_or_else(|| format!("Group {} not found", group_id))?;
+        if !members.contains(&session_id) {
+            members.push(session_id);
+        }
+        Ok(())
+    }
+
+    /// Remove a session from a group; returns false if it was no member
+    pub fn remove_from_group(&self, group_id: &str, session_id: &str) -> Result<bool, String> {
+        let mut groups = self.groups.write();
+        let members = groups
+            .get_mut(group_id)
+            .ok_or_else(|| format!("Group {} not found", group_id))?;
+        let before = members.len();
+        members.retain(|member| member != session_id);
+        Ok(members.len() != before)
+    }
+
+    /// Delete a group, leaving its sessions running
+    pub fn delete_group(&self, group_id: &str) -> bool {
+        self.groups.write().remove(group_id).is_some()
+    }
+
+    /// Sessions in a group, in the order they were added
+    pub fn group_members(&self, group_id: &str) -> Result<Vec<String>, String> {
+        self.groups
+            .read()
+            .get(group_id)
+            .cloned()
+            .ok_or_else(|| format!("Group {} not found", group_id))
+    }
+
+    /// Write the same input to every session in a group.
+    ///
+    /// Members that were closed or whose process exited are reported as
+    /// failed and removed from the group.
+    pub fn write_to_group(
+        &self,
+        group_id: &str,
+        data: &[u8],
+    ) -> Result<Vec<GroupWriteResult>, String> {
+        let members = self.group_members(group_id)?;
+        let mut dead = Vec::new();
+        let results = {
+            let sessions = self.sessions.read();
+            members
+                .into_iter()
+                .map(|session_id| {
+                    let result = match sessions.get(&session_id) {
+                        None => Err(format!("Session {} not found", session_id)),
+                        Some(session) if session.exit_status().is_some() => {
+                            Err(format!("Session {} has exited", session_id))
+                        }
+                        Some(session) => session.write(data),
+                    };
+                    let alive = sessions
+                        .get(&session_id)
+                        .is_some_and(|session| session.exit_status().is_none());
+                    if !alive {
+                        dead.push(session_id.clone());
+                    }
+                    GroupWriteResult {
+                        session_id,
+                        ok: result.is_ok(),
+                        error: result.err(),
+                    }
+                })
+                .collect()
+        };
+
+        if !dead.is_empty() {
+            if let Some(members) = self.groups.write().get_mut(group_id) {
+                members.retain(|member| !dead.contains(member));
+            }
         }
+        Ok(results)
+    }
+
+    /// Describe all sessions carrying every label in `labels`, oldest first
+    pub fn session_infos(&self, labels: &HashMap<String, String>) -> Vec<TerminalSessionInfo> {
+        let mut infos: Vec<TerminalSessionInfo> = self
+            .sessions
+            .read()
+            .values()
+            .map(|session| session.info())
+            .filter(|info| info.matches_labels(labels))
+            .collect();
+        infos.sort_by_key(|info| info.created_at);
+        infos
     }
 
     /// List all active session IDs
@@ -91,3 +510,165 @@ impl PtyState {
         sessions.contains_key(session_id)
     }
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::pty::procfs;
+    use std::fs;
+    use std::thread;
+    use std::time::Instant;
+
+    /// Pids whose command line contains `needle` as an argument
+    fn find_processes(needle: &str) -> Vec<i32> {
+        fs::read_dir("/proc")
+            .unwrap()
+            .flatten()
+            .filter_map(|entry| entry.file_name().to_str()?.parse::<i32>().ok())
+            .filter(|pid| {
+                fs::read(format!("/proc/{}/cmdline", pid))
+                    .map(|cmdline| {
+                        cmdline
+                            .split(|&b| b == 0)
+                            .any(|arg| arg == needle.as_bytes())
+                    })
+                    .unwrap_or(false)
+            })
+            .filter(|&pid| procfs::read_stat(pid).is_some_and(|stat| !stat.is_zombie()))
+            .collect()
+    }
+
+    /// Spawn a shell session running `script` and wait until `marker` shows up
+    fn spawn_with_sleeper(state: &PtyState, session_id: &str, script: &str, marker: &str) -> i32 {
+        state
+            .spawn_session(
+                session_id.to_string(),
+                SpawnOptions::default(),
+                EventEmitter::discard(),
+            )
+            .unwrap();
+        state
+            .write_to_session(session_id, format!("{}\n", script).as_bytes())
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            if let Some(&pid) = find_processes(marker).first() {
+                return procfs::read_stat(pid).unwrap().session;
+            }
+            assert!(Instant::now() < deadline, "sleep never started");
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn test_close_session_kills_foreground_and_background_jobs() {
+        let state = PtyState::new();
+        let (bg, fg) = ("3000017", "3000018");
+        let sid = spawn_with_sleeper(
+            &state,
+            "close-jobs",
+            &format!("sleep {} & sleep {}", bg, fg),
+            fg,
+        );
+        assert!(!find_processes(bg).is_empty());
+
+        state
+            .close_session("close-jobs", Duration::from_millis(200))
+            .unwrap();
+
+        assert!(find_processes(bg).is_empty());
+        assert!(find_processes(fg).is_empty());
+        assert!(procfs::session_processes(sid).is_empty());
+        assert!(!state.has_session("close-jobs"));
+    }
+
+    #[test]
+    fn test_write_to_group_drops_dead_sessions() {
+        let state = PtyState::new();
+        for (session_id, command) in [("group-a", "cat"), ("group-b", "cat"), ("group-c", "true")] {
+            let options = SpawnOptions {
+                command: Some(command.to_string()),
+                ..Default::default()
+            };
+            state
+                .spawn_session(session_id.to_string(), options, EventEmitter::discard())
+                .unwrap();
+        }
+        state
+            .create_group(
+                "agents".into(),
+                vec!["group-a".into(), "group-b".into(), "group-c".into()],
+            )
+            .unwrap();
+        assert!(state.create_group("agents".into(), Vec::new()).is_err());
+        assert!(state.add_to_group("agents", "missing".into()).is_err());
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while state.session_exit("group-c").unwrap().is_none() {
+            assert!(Instant::now() < deadline, "true never exited");
+            thread::sleep(Duration::from_millis(20));
+        }
+        state
+            .close_session("group-b", Duration::from_millis(100))
+            .unwrap();
+
+        let results = state.write_to_group("agents", b"hello\n").unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].ok);
+        assert_eq!(results[1].session_id, "group-c");
+        assert!(!results[1].ok);
+        assert_eq!(state.group_members("agents").unwrap(), ["group-a"]);
+
+        assert!(state.remove_from_group("agents", "group-a").unwrap());
+        assert!(state.delete_group("agents"));
+        let _ = state.close_session("group-a", Duration::from_millis(100));
+        let _ = state.close_session("group-c", Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_sessions_are_reaped_after_their_lifetime() {
+        let state = PtyState::new();
+        let options = SpawnOptions {
+            command: Some("cat".to_string()),
+            ..Default::default()
+        };
+        state
+            .spawn_session("reap-me".into(), options, EventEmitter::discard())
+            .unwrap();
+        state
+            .set_session_timeouts(
+                "reap-me",
+                SessionTimeouts {
+                    max_lifetime_ms: Some(100),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while state.has_session("reap-me") {
+            assert!(Instant::now() < deadline, "session was not reaped");
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn test_close_session_escalates_to_sigkill() {
+        let state = PtyState::new();
+        let marker = "3000019";
+        let sid = spawn_with_sleeper(
+            &state,
+            "close-stubborn",
+            &format!("sh -c \"trap '' HUP TERM; sleep {}\"", marker),
+            marker,
+        );
+
+        state
+            .close_session("close-stubborn", Duration::from_millis(100))
+            .unwrap();
+
+        assert!(find_processes(marker).is_empty());
+        assert!(procfs::session_processes(sid).is_empty());
+    }
+}
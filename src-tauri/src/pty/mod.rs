@@ -3,7 +3,38 @@
 //! Provides native terminal support for the Tauri application.
 //! Uses portable-pty for cross-platform PTY handling.
 
+pub mod agent;
+pub mod ansi;
+pub mod child;
+pub mod cwd;
+#[cfg(unix)]
+pub mod daemon;
+pub mod env;
+pub mod events;
+pub mod flow;
+pub mod foreground;
+pub mod history;
+pub mod info;
+pub mod limits;
 pub mod manager;
+pub mod osc;
+pub mod output;
+#[cfg(target_os = "linux")]
+pub mod procfs;
+pub mod profiles;
+pub mod recording;
+pub mod redact;
+pub mod sandbox;
+pub mod screen;
+pub mod script;
+pub mod scrollback;
+pub mod secrets;
 pub mod session;
+#[cfg(unix)]
+pub mod shell_integration;
+pub mod stats;
+pub mod timeouts;
+pub mod utf8;
+pub mod watch;
 
 pub use manager::PtyState;
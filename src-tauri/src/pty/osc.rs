@@ -0,0 +1,190 @@
+//! OSC Sequence Scanning
+//!
+//! Picks Operating System Command sequences (`ESC ] code ; data`, ended by
+//! BEL or `ESC \`) out of session output, e.g. the working directory
+//! reports shells send as OSC 7. Sequences may be split across chunks.
+
+/// Longest OSC sequence kept; longer ones (e.g. inline images) are skipped
+const MAX_OSC_BYTES: usize = 4096;
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// A complete OSC sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Osc {
+    pub code: u16,
+    pub data: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Osc,
+    OscEscape,
+}
+
+/// Incremental OSC scanner
+pub struct OscScanner {
+    state: State,
+    buffer: Vec<u8>,
+    overflowed: bool,
+}
+
+impl Default for OscScanner {
+    fn default() -> Self {
+        Self {
+            state: State::Ground,
+            buffer: Vec::new(),
+            overflowed: false,
+        }
+    }
+}
+
+impl OscScanner {
+    /// Scan a chunk of output, returning the sequences completed in it
+    /// together with the offset in `data` just past each one
+    pub fn feed(&mut self, data: &[u8]) -> Vec<(usize, Osc)> {
+        let mut found = Vec::new();
+        for (i, &byte) in data.iter().enumerate() {
+            self.state = match (self.state, byte) {
+                (State::Ground, ESC) => State::Escape,
+                (State::Ground, _) => State::Ground,
+                (State::Escape, b']') => {
+                    self.buffer.clear();
+                    self.overflowed = false;
+                    State::Osc
+                }
+                (State::Escape, ESC) => State::Escape,
+                (State::Escape, _) => State::Ground,
+                (State::Osc, BEL) | (State::OscEscape, b'\\') => {
+                    found.extend(self.finish().map(|osc| (i + 1, osc)));
+                    State::Ground
+                }
+                (State::Osc, ESC) => State::OscEscape,
+                (State::Osc, _) => {
+                    if self.buffer.len() < MAX_OSC_BYTES {
+                        self.buffer.push(byte);
+                    } else {
+                        self.overflowed = true;
+                    }
+                    State::Osc
+                }
+                // Any other escape aborts the sequence and may start a new one
+                (State::OscEscape, b']') => {
+                    self.buffer.clear();
+                    self.overflowed = false;
+                    State::Osc
+                }
+                (State::OscEscape, ESC) => State::Escape,
+                (State::OscEscape, _) => State::Ground,
+            };
+        }
+        found
+    }
+
+    fn finish(&mut self) -> Option<Osc> {
+        if self.overflowed {
+            return None;
+        }
+        let text = String::from_utf8_lossy(&self.buffer);
+        let (code, data) = text.split_once(';').unwrap_or((&text, ""));
+        Some(Osc {
+            code: code.parse().ok()?,
+            data: data.to_string(),
+        })
+    }
+}
+
+/// Local path of an OSC 7 `file://host/path` URL
+pub fn file_url_path(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    Some(percent_decode(path))
+}
+
+/// Decode `%XX` escapes, leaving malformed ones as they are
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scanner_finds_sequences_split_across_chunks() {
+        let mut scanner = OscScanner::default();
+        assert!(scanner.feed(b"ls\r\n\x1b]7;file://host/tmp/a").is_empty());
+        let found = scanner.feed(b"%20b\x1b\\\x1b]0;title\x07\x1b[0m$ ");
+
+        assert_eq!(
+            found,
+            [
+                (
+                    6,
+                    Osc {
+                        code: 7,
+                        data: "file://host/tmp/a%20b".into()
+                    }
+                ),
+                (
+                    16,
+                    Osc {
+                        code: 0,
+                        data: "title".into()
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scanner_skips_oversized_sequences() {
+        let mut scanner = OscScanner::default();
+        let mut data = b"\x1b]1337;File=".to_vec();
+        data.resize(data.len() + MAX_OSC_BYTES, b'A');
+        data.extend_from_slice(b"\x07\x1b]133;A\x07");
+
+        assert_eq!(
+            scanner.feed(&data),
+            [(
+                data.len(),
+                Osc {
+                    code: 133,
+                    data: "A".into()
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_file_url_path() {
+        assert_eq!(
+            file_url_path("file://mac.local/Users/me/my%20repo").as_deref(),
+            Some("/Users/me/my repo")
+        );
+        assert_eq!(file_url_path("file:///tmp").as_deref(), Some("/tmp"));
+        assert_eq!(file_url_path("https://example.com/x"), None);
+    }
+}
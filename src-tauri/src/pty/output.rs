@@ -0,0 +1,405 @@
+//! PTY Output Delivery
+//!
+//! Routes session output to the frontend, either as broadcast
+//! `terminal-output` events or as raw binary frames over an IPC channel
+//! owned by the window that displays the session.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::SystemTime;
+use tauri::ipc::{Channel, InvokeResponseBody};
+
+use super::agent::{AgentDetector, AgentState};
+use super::cwd::CwdTracker;
+use super::events::EventEmitter;
+use super::foreground::ForegroundProcess;
+use super::history::{CommandHistory, CommandRecord};
+use super::osc::{Osc, OscScanner};
+use super::recording::Recorder;
+use super::redact::Redactor;
+use super::screen::Screen;
+use super::scrollback::Scrollback;
+use super::session::{
+    TerminalAgentStatePayload, TerminalCwdPayload, TerminalMatchPayload, TerminalOutputPayload,
+};
+use super::watch::{WatchMatch, Watchers};
+
+/// IPC channel carrying raw output frames to a single window
+pub type OutputChannel = Channel<InvokeResponseBody>;
+
+/// Size of the stream offset header at the start of every channel frame
+pub const FRAME_HEADER_BYTES: usize = 8;
+
+/// Encode a channel frame: the little-endian stream offset of the first
+/// byte, followed by the output bytes themselves
+pub fn encode_frame(seq: u64, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_BYTES + data.len());
+    frame.extend_from_slice(&seq.to_le_bytes());
+    frame.extend_from_slice(data);
+    frame
+}
+
+/// Scrollback, screen state and delivery target of a session's output.
+///
+/// All of them live behind one lock so that attaching a new channel and
+/// snapshotting the scrollback or screen happen atomically with respect to
+/// output.
+pub struct OutputStream {
+    session_id: String,
+    scrollback: Scrollback,
+    screen: Screen,
+    osc: OscScanner,
+    cwd: CwdTracker,
+    history: CommandHistory,
+    watchers: Watchers,
+    agent: AgentDetector,
+    /// Masks secrets before output goes anywhere else
+    redactor: Option<Redactor>,
+    /// Receivers of a copy of all output, e.g. running scripts
+    taps: Vec<mpsc::Sender<Vec<u8>>>,
+    recorder: Option<Recorder>,
+    channel: Option<OutputChannel>,
+    events: EventEmitter,
+    last_input: SystemTime,
+    last_output: SystemTime,
+}
+
+impl OutputStream {
+    /// Create a stream delivering to `channel`, or as events when absent
+    pub fn new(
+        session_id: String,
+        scrollback_bytes: usize,
+        screen: Screen,
+        channel: Option<OutputChannel>,
+        events: EventEmitter,
+    ) -> Self {
+        Self {
+            session_id,
+            scrollback: Scrollback::new(scrollback_bytes),
+            screen,
+            osc: OscScanner::default(),
+            cwd: CwdTracker::new(None, None),
+            history: CommandHistory::default(),
+            watchers: Watchers::default(),
+            agent: AgentDetector::default(),
+            redactor: None,
+            taps: Vec::new(),
+            recorder: None,
+            channel,
+            events,
+            last_input: SystemTime::now(),
+            last_output: SystemTime::now(),
+        }
+    }
+
+    /// Record a chunk in the scrollback and deliver it, after masking
+    /// secrets. Output that may be the start of a secret is held back.
+    ///
+    /// Returns the stream offset just past the delivered output.
+    pub fn publish(&mut self, data: Vec<u8>) -> u64 {
+        let data = match &mut self.redactor {
+            Some(redactor) => redactor.feed(&data),
+            None => data,
+        };
+        self.deliver(data)
+    }
+
+    /// Deliver output held back by redaction, e.g. because no more arrived
+    pub fn release_held(&mut self) -> u64 {
+        let data = self
+            .redactor
+            .as_mut()
+            .map(Redactor::flush)
+            .unwrap_or_default();
+        self.deliver(data)
+    }
+
+    /// Whether redaction holds back output waiting for more
+    pub fn is_holding(&self) -> bool {
+        self.redactor.as_ref().is_some_and(Redactor::is_holding)
+    }
+
+    /// Mask secrets in the output from now on
+    pub fn set_redactor(&mut self, redactor: Option<Redactor>) {
+        self.redactor = redactor;
+    }
+
+    /// Number of secrets masked so far, by rule
+    pub fn redactions(&self) -> BTreeMap<String, u64> {
+        self.redactor
+            .as_ref()
+            .map(Redactor::counts)
+            .unwrap_or_default()
+    }
+
+    fn deliver(&mut self, data: Vec<u8>) -> u64 {
+        if data.is_empty() {
+            return self.scrollback.end_seq();
+        }
+        let seq = self.scrollback.push(&data);
+        self.last_output = SystemTime::now();
+        self.screen.process(&data);
+        let sequences = self.osc.feed(&data);
+        self.track_cwd(&sequences);
+        self.history
+            .feed(seq, &data, &sequences, self.cwd.current());
+        let matches = self.watchers.feed(&data);
+        self.emit_matches(matches);
+        self.agent.observe(&data);
+        self.record(|recorder| recorder.output(&data));
+        self.taps.retain(|tap| tap.send(data.clone()).is_ok());
+        let end_seq = seq + data.len() as u64;
+
+        if let Some(channel) = &self.channel {
+            let frame = InvokeResponseBody::Raw(encode_frame(seq, &data));
+            if channel.send(frame).is_ok() {
+                return end_seq;
+            }
+            // The owning window is gone; fall back to events until it reattaches
+            self.channel = None;
+        }
+
+        self.events.emit(
+            "terminal-output",
+            TerminalOutputPayload {
+                session_id: self.session_id.clone(),
+                data,
+                seq,
+            },
+        );
+        end_seq
+    }
+
+    /// Replace the tracker following the session's working directory
+    pub fn set_cwd_tracker(&mut self, tracker: CwdTracker) {
+        self.cwd = tracker;
+    }
+
+    /// Last known working directory of the session
+    pub fn cwd(&self) -> Option<&str> {
+        self.cwd.current()
+    }
+
+    /// Commands run in the session, at most the last `limit` of them
+    pub fn command_history(&self, limit: Option<usize>) -> Vec<CommandRecord> {
+        self.history.records(limit)
+    }
+
+    /// Receive a copy of all output from now on, until the receiver is
+    /// dropped
+    pub fn tap(&mut self) -> mpsc::Receiver<Vec<u8>> {
+        let (tx, rx) = mpsc::channel();
+        self.taps.push(tx);
+        rx
+    }
+
+    /// What the agent running in the session is doing
+    pub fn agent_state(&self) -> AgentState {
+        self.agent.state()
+    }
+
+    /// Re-evaluate the agent state against the screen, announcing changes
+    pub fn update_agent(&mut self, foreground: Option<&ForegroundProcess>, exited: bool) {
+        let screen = self.screen.contents();
+        if let Some(state) = self.agent.update(&screen, foreground, exited) {
+            self.events.emit(
+                "terminal-agent-state",
+                TerminalAgentStatePayload {
+                    session_id: self.session_id.clone(),
+                    state,
+                },
+            );
+        }
+    }
+
+    /// Match `pattern` against the output from now on
+    pub fn watch(&mut self, watcher_id: String, pattern: &str, once: bool) -> Result<(), String> {
+        self.watchers.add(watcher_id, pattern, once)
+    }
+
+    /// Remove an output watcher; returns false if it did not exist
+    pub fn unwatch(&mut self, watcher_id: &str) -> bool {
+        self.watchers.remove(watcher_id)
+    }
+
+    /// Report matches held back at the end of the output once it went quiet
+    pub fn settle_watchers(&mut self) {
+        let matches = self.watchers.settle();
+        self.emit_matches(matches);
+    }
+
+    fn emit_matches(&self, matches: Vec<WatchMatch>) {
+        for watch_match in matches {
+            self.events.emit(
+                "terminal-match",
+                TerminalMatchPayload {
+                    session_id: self.session_id.clone(),
+                    watch_match,
+                },
+            );
+        }
+    }
+
+    /// Update the working directory from OSC 7 reports in the output,
+    /// announcing changes
+    fn track_cwd(&mut self, sequences: &[(usize, Osc)]) {
+        let mut changed = None;
+        for (_, osc) in sequences {
+            if osc.code == 7 {
+                changed = self.cwd.report(&osc.data).or(changed);
+            }
+        }
+        self.emit_cwd(changed);
+    }
+
+    /// Look up the working directory of the foreground process unless the
+    /// shell reports it, announcing changes. Called periodically rather
+    /// than per output batch, as it reads `/proc`.
+    pub fn poll_cwd(&mut self) {
+        let changed = self.cwd.poll();
+        self.emit_cwd(changed);
+    }
+
+    fn emit_cwd(&self, changed: Option<String>) {
+        if let Some(cwd) = changed {
+            self.events.emit(
+                "terminal-cwd-changed",
+                TerminalCwdPayload {
+                    session_id: self.session_id.clone(),
+                    cwd,
+                },
+            );
+        }
+    }
+
+    /// Headless emulator state of the session's screen
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    /// Follow a PTY resize in the screen model and recording
+    pub fn resize(&mut self, rows: u16, cols: u16) {
+        self.screen.resize(rows, cols);
+        self.record(|recorder| recorder.resize(cols, rows));
+    }
+
+    /// Note input written to the session, recording it if recording
+    pub fn input(&mut self, data: &[u8]) {
+        self.last_input = SystemTime::now();
+        self.record(|recorder| recorder.input(data));
+    }
+
+    /// Time input was last written to the session
+    pub fn last_input(&self) -> SystemTime {
+        self.last_input
+    }
+
+    /// Time the session last printed anything
+    pub fn last_output(&self) -> SystemTime {
+        self.last_output
+    }
+
+    /// Start recording to a new `.cast` file in the project directory
+    pub fn start_recording(&mut self, project_dir: &Path) -> Result<PathBuf, String> {
+        if let Some(recorder) = &self.recorder {
+            return Err(format!(
+                "Session {} is already recording to {}",
+                self.session_id,
+                recorder.path().display()
+            ));
+        }
+        let (rows, cols) = self.screen.size();
+        let recorder = Recorder::create(project_dir, &self.session_id, cols, rows)
+            .map_err(|e| format!("Failed to start recording: {}", e))?;
+        let path = recorder.path().to_path_buf();
+        self.recorder = Some(recorder);
+        Ok(path)
+    }
+
+    /// Stop recording, returning the finished file if one was being written
+    pub fn stop_recording(&mut self) -> Result<Option<PathBuf>, String> {
+        self.recorder
+            .take()
+            .map(|recorder| recorder.finish())
+            .transpose()
+            .map_err(|e| format!("Failed to finish recording: {}", e))
+    }
+
+    /// Apply an event to the active recorder, dropping it on write errors
+    fn record(&mut self, event: impl FnOnce(&mut Recorder) -> std::io::Result<()>) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = event(recorder) {
+                eprintln!("Recording for {} stopped: {}", self.session_id, e);
+                self.recorder = None;
+            }
+        }
+    }
+
+    /// Snapshot the scrollback, switching delivery to `channel` if given
+    pub fn attach(&mut self, channel: Option<OutputChannel>) -> (Vec<u8>, u64) {
+        if channel.is_some() {
+            self.channel = channel;
+        }
+        self.scrollback.snapshot()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn recording_channel() -> (OutputChannel, Arc<Mutex<Vec<Vec<u8>>>>) {
+        let frames = Arc::new(Mutex::new(Vec::new()));
+        let sink = frames.clone();
+        let channel = Channel::new(move |body| {
+            if let InvokeResponseBody::Raw(frame) = body {
+                sink.lock().unwrap().push(frame);
+            }
+            Ok(())
+        });
+        (channel, frames)
+    }
+
+    #[test]
+    fn test_channel_frames_carry_stream_offset() {
+        let (channel, frames) = recording_channel();
+        let mut stream = OutputStream::new(
+            "s".into(),
+            1024,
+            Screen::new(24, 80),
+            Some(channel),
+            EventEmitter::discard(),
+        );
+        stream.publish(b"abc".to_vec());
+        stream.publish(b"de".to_vec());
+
+        let frames = frames.lock().unwrap();
+        assert_eq!(frames[0], encode_frame(0, b"abc"));
+        assert_eq!(frames[1][..FRAME_HEADER_BYTES], 3u64.to_le_bytes());
+        assert_eq!(&frames[1][FRAME_HEADER_BYTES..], b"de");
+    }
+
+    #[test]
+    fn test_attach_replaces_channel() {
+        let (first, first_frames) = recording_channel();
+        let (second, second_frames) = recording_channel();
+        let mut stream = OutputStream::new(
+            "s".into(),
+            1024,
+            Screen::new(24, 80),
+            Some(first),
+            EventEmitter::discard(),
+        );
+        stream.publish(b"one".to_vec());
+
+        let (data, seq) = stream.attach(Some(second));
+        stream.publish(b"two".to_vec());
+
+        assert_eq!(data, b"one");
+        assert_eq!(seq, 3);
+        assert_eq!(first_frames.lock().unwrap().len(), 1);
+        assert_eq!(second_frames.lock().unwrap()[0], encode_frame(3, b"two"));
+    }
+}
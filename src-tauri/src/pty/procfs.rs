@@ -0,0 +1,182 @@
+//! /proc Process Inspection
+//!
+//! Minimal readers for Linux `/proc` entries used to find the processes
+//! that belong to a PTY session and measure what they use.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Fields of `/proc/<pid>/stat` that the PTY module cares about
+#[derive(Debug, Clone)]
+pub struct ProcStat {
+    pub pid: i32,
+    pub state: char,
+    pub ppid: i32,
+    pub pgrp: i32,
+    pub session: i32,
+    /// Foreground process group of the controlling terminal
+    pub tpgid: i32,
+    /// Time spent in user and kernel mode, in clock ticks
+    pub cpu_ticks: u64,
+    pub threads: u64,
+    /// Resident set size in pages
+    pub rss_pages: u64,
+}
+
+impl ProcStat {
+    /// Whether the process has exited but not been reaped yet
+    pub fn is_zombie(&self) -> bool {
+        self.state == 'Z' || self.state == 'X'
+    }
+}
+
+/// Parse the contents of a `/proc/<pid>/stat` file
+pub fn parse_stat(contents: &str) -> Option<ProcStat> {
+    // comm is wrapped in parentheses and may itself contain spaces or ')'
+    let close = contents.rfind(')')?;
+    let pid = contents.split_whitespace().next()?.parse().ok()?;
+
+    let mut fields = contents[close + 1..].split_whitespace();
+    let state = fields.next()?.chars().next()?;
+    let ppid = fields.next()?.parse().ok()?;
+    let pgrp = fields.next()?.parse().ok()?;
+    let session = fields.next()?.parse().ok()?;
+    let _tty_nr = fields.next()?;
+    let tpgid = fields.next()?.parse().ok()?;
+
+    // Usage fields by their number in proc(5), counting from flags (9)
+    let usage: Vec<u64> = fields.map(|field| field.parse().unwrap_or(0)).collect();
+    let field = |n: usize| usage.get(n - 9).copied().unwrap_or(0);
+
+    Some(ProcStat {
+        pid,
+        state,
+        ppid,
+        pgrp,
+        session,
+        tpgid,
+        cpu_ticks: field(14) + field(15),
+        threads: field(20),
+        rss_pages: field(24),
+    })
+}
+
+/// Read `/proc/<pid>/stat`
+pub fn read_stat(pid: i32) -> Option<ProcStat> {
+    let contents = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    parse_stat(&contents)
+}
+
+/// Working directory of a process
+pub fn cwd(pid: i32) -> Option<PathBuf> {
+    fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+/// Number of file descriptors a process has open
+pub fn open_fds(pid: i32) -> Option<u64> {
+    let entries = fs::read_dir(format!("/proc/{}/fd", pid)).ok()?;
+    Some(entries.count() as u64)
+}
+
+/// All processes currently listed in `/proc`
+pub fn all_processes() -> Vec<ProcStat> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<i32>().ok())
+        .filter_map(read_stat)
+        .collect()
+}
+
+/// Live (non-zombie) processes belonging to the given session
+pub fn session_processes(session: i32) -> Vec<ProcStat> {
+    all_processes()
+        .into_iter()
+        .filter(|stat| stat.session == session && !stat.is_zombie())
+        .collect()
+}
+
+/// `root` and every live process descending from it in `processes`
+pub fn tree(processes: Vec<ProcStat>, root: i32) -> Vec<ProcStat> {
+    let mut children: HashMap<i32, Vec<ProcStat>> = HashMap::new();
+    let mut tree = Vec::new();
+    for stat in processes.into_iter().filter(|stat| !stat.is_zombie()) {
+        if stat.pid == root {
+            tree.push(stat);
+        } else {
+            children.entry(stat.ppid).or_default().push(stat);
+        }
+    }
+
+    let mut next = 0;
+    while next < tree.len() {
+        if let Some(descendants) = children.remove(&tree[next].pid) {
+            tree.extend(descendants);
+        }
+        next += 1;
+    }
+    tree
+}
+
+/// Live processes in the tree under `root`, `root` included
+pub fn process_tree(root: i32) -> Vec<ProcStat> {
+    tree(all_processes(), root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stat() {
+        let stat = parse_stat("4242 (sleep) S 4200 4242 4200 34817 4242 4194304 90 0").unwrap();
+        assert_eq!(stat.state, 'S');
+        assert_eq!(stat.pgrp, 4242);
+        assert_eq!(stat.session, 4200);
+        assert_eq!(stat.tpgid, 4242);
+    }
+
+    #[test]
+    fn test_parse_stat_usage() {
+        let stat = parse_stat(
+            "4242 (node) R 4200 4242 4200 34817 4242 4194304 90 0 0 0 150 25 0 0 20 0 11 0 \
+             81234 1093246976 23456 18446744073709551615",
+        )
+        .unwrap();
+        assert_eq!(stat.pid, 4242);
+        assert_eq!(stat.ppid, 4200);
+        assert_eq!(stat.cpu_ticks, 175);
+        assert_eq!(stat.threads, 11);
+        assert_eq!(stat.rss_pages, 23456);
+    }
+
+    #[test]
+    fn test_tree_follows_descendants() {
+        let stat = |pid: i32, ppid: i32, state: &str| {
+            parse_stat(&format!("{} (p) {} {} {} 1 0 -1", pid, state, ppid, pid)).unwrap()
+        };
+        let processes = vec![
+            stat(30, 20, "S"),
+            stat(20, 10, "S"),
+            stat(10, 1, "S"),
+            stat(11, 1, "S"),
+            stat(21, 10, "Z"),
+            stat(40, 11, "S"),
+        ];
+
+        let pids: Vec<i32> = tree(processes, 10).iter().map(|stat| stat.pid).collect();
+        assert_eq!(pids, [10, 20, 30]);
+    }
+
+    #[test]
+    fn test_parse_stat_comm_with_parens_and_spaces() {
+        let stat = parse_stat("17 (tmux: server) (x)) Z 1 17 17 0 -1").unwrap();
+        assert!(stat.is_zombie());
+        assert_eq!(stat.pgrp, 17);
+        assert_eq!(stat.session, 17);
+    }
+}
@@ -0,0 +1,125 @@
+//! Shell Profiles
+//!
+//! Named shell configurations kept in the app config directory, so users
+//! of fish or nushell, or agents that need a clean non-login environment,
+//! are not stuck with `$SHELL -l -i`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File in the app config directory holding the profiles
+const PROFILES_FILE: &str = "shell-profiles.json";
+
+/// How to start the shell of a session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShellProfile {
+    pub name: String,
+    /// Shell executable, e.g. `/usr/bin/fish` or `nu`
+    pub program: String,
+    /// Arguments passed instead of `-l -i`
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Variables set for the shell; `env` given at spawn time wins
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Typed into the shell once it shows its first prompt, e.g. `claude`
+    #[serde(default)]
+    pub initial_command: Option<String>,
+    /// `TERM` value, defaults to `xterm-256color`
+    #[serde(default)]
+    pub term: Option<String>,
+}
+
+/// Path of the profiles file in `config_dir`
+pub fn profiles_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(PROFILES_FILE)
+}
+
+/// Read the profiles; no file means no profiles
+pub fn load(path: &Path) -> Result<Vec<ShellProfile>, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read shell profiles: {}", e)),
+    };
+    serde_json::from_str(&contents).map_err(|e| format!("Invalid shell profiles: {}", e))
+}
+
+/// Validate and store the profiles, replacing all existing ones
+pub fn save(path: &Path, profiles: &[ShellProfile]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for profile in profiles {
+        if profile.name.trim().is_empty() {
+            return Err("Shell profile names must not be empty".to_string());
+        }
+        if profile.program.trim().is_empty() {
+            return Err(format!("Shell profile {} has no program", profile.name));
+        }
+        if !names.insert(profile.name.as_str()) {
+            return Err(format!("Duplicate shell profile {}", profile.name));
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    let json = serde_json::to_string_pretty(profiles)
+        .map_err(|e| format!("Failed to serialize shell profiles: {}", e))?;
+    // Write a temporary file first so a crash never leaves half a file
+    let partial = path.with_extension("json.tmp");
+    fs::write(&partial, json)
+        .and_then(|_| fs::rename(&partial, path))
+        .map_err(|e| format!("Failed to write shell profiles: {}", e))
+}
+
+/// Look up a profile by name
+pub fn find(path: &Path, name: &str) -> Result<ShellProfile, String> {
+    load(path)?
+        .into_iter()
+        .find(|profile| profile.name == name)
+        .ok_or_else(|| format!("Shell profile {} not found", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str) -> ShellProfile {
+        ShellProfile {
+            name: name.to_string(),
+            program: "/usr/bin/fish".to_string(),
+            args: vec!["-i".to_string()],
+            env: HashMap::new(),
+            initial_command: None,
+            term: None,
+        }
+    }
+
+    #[test]
+    fn test_save_and_find_profiles() {
+        let dir = std::env::temp_dir().join(format!("specflux-profiles-{}", std::process::id()));
+        let path = profiles_path(&dir);
+        assert!(load(&path).unwrap().is_empty());
+
+        save(&path, &[profile("fish"), profile("agent")]).unwrap();
+        assert_eq!(load(&path).unwrap().len(), 2);
+        assert_eq!(find(&path, "agent").unwrap(), profile("agent"));
+        assert!(find(&path, "nu").is_err());
+
+        assert!(save(&path, &[profile("fish"), profile("fish")]).is_err());
+        assert!(save(&path, &[profile(" ")]).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_profile_fields_default() {
+        let profiles: Vec<ShellProfile> =
+            serde_json::from_str(r#"[{"name": "nu", "program": "nu", "term": "xterm"}]"#).unwrap();
+        assert!(profiles[0].args.is_empty());
+        assert_eq!(profiles[0].term.as_deref(), Some("xterm"));
+    }
+}
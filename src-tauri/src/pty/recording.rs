@@ -0,0 +1,270 @@
+//! Asciicast Session Recording
+//!
+//! Records session output, input and resizes to an asciicast v2 `.cast`
+//! file so what an agent did can be reviewed (or replayed with asciinema)
+//! after the fact.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use super::utf8;
+
+/// Recordings live under the project's `.specflux` directory
+const RECORDINGS_DIR: &str = ".specflux/recordings";
+
+/// Header line of an asciicast v2 file
+#[derive(Serialize, Deserialize)]
+struct CastHeader {
+    version: u8,
+    width: u16,
+    height: u16,
+    timestamp: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+}
+
+/// Summary of a recording file
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingInfo {
+    pub path: String,
+    pub title: Option<String>,
+    /// Unix time the recording started
+    pub timestamp: u64,
+    pub width: u16,
+    pub height: u16,
+    #[serde(rename = "sizeBytes")]
+    pub size_bytes: u64,
+}
+
+/// Directory holding the recordings of a project
+pub fn recordings_dir(project_dir: &Path) -> PathBuf {
+    project_dir.join(RECORDINGS_DIR)
+}
+
+/// Writes asciicast v2 events for one session
+pub struct Recorder {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    started: Instant,
+    // Partial UTF-8 characters carried over to the next event
+    output_tail: Vec<u8>,
+    input_tail: Vec<u8>,
+}
+
+impl Recorder {
+    /// Start a new recording in the project's recordings directory
+    pub fn create(project_dir: &Path, session_id: &str, cols: u16, rows: u16) -> io::Result<Self> {
+        let dir = recordings_dir(project_dir);
+        fs::create_dir_all(&dir)?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let safe_id: String = session_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let (path, file) = Self::create_file(&dir, &safe_id, now.as_millis())?;
+
+        let mut writer = BufWriter::new(file);
+        let header = CastHeader {
+            version: 2,
+            width: cols,
+            height: rows,
+            timestamp: now.as_secs(),
+            title: Some(session_id.to_string()),
+        };
+        serde_json::to_writer(&mut writer, &header)?;
+        writer.write_all(b"\n")?;
+
+        Ok(Self {
+            writer,
+            path,
+            started: Instant::now(),
+            output_tail: Vec::new(),
+            input_tail: Vec::new(),
+        })
+    }
+
+    /// Create a file named after the session and the start time, never
+    /// replacing an earlier recording started in the same millisecond
+    fn create_file(dir: &Path, safe_id: &str, millis: u128) -> io::Result<(PathBuf, File)> {
+        let mut attempt = 0;
+        loop {
+            let name = match attempt {
+                0 => format!("{}-{}.cast", safe_id, millis),
+                n => format!("{}-{}-{}.cast", safe_id, millis, n),
+            };
+            let path = dir.join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok((path, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Path of the `.cast` file being written
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record bytes the session printed
+    pub fn output(&mut self, data: &[u8]) -> io::Result<()> {
+        let text = Self::decode(&mut self.output_tail, data);
+        self.event("o", &text)
+    }
+
+    /// Record bytes written to the session
+    pub fn input(&mut self, data: &[u8]) -> io::Result<()> {
+        let text = Self::decode(&mut self.input_tail, data);
+        self.event("i", &text)
+    }
+
+    /// Record a terminal resize
+    pub fn resize(&mut self, cols: u16, rows: u16) -> io::Result<()> {
+        self.event("r", &format!("{}x{}", cols, rows))
+    }
+
+    /// Flush and close the recording, returning its path
+    pub fn finish(mut self) -> io::Result<PathBuf> {
+        self.writer.flush()?;
+        Ok(self.path)
+    }
+
+    fn event(&mut self, code: &str, data: &str) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let elapsed = self.started.elapsed().as_secs_f64();
+        serde_json::to_writer(&mut self.writer, &(elapsed, code, data))?;
+        self.writer.write_all(b"\n")
+    }
+
+    /// Decode as text, keeping a trailing partial character for next time
+    fn decode(tail: &mut Vec<u8>, data: &[u8]) -> String {
+        tail.extend_from_slice(data);
+        let complete = tail.len() - utf8::incomplete_tail_len(tail);
+        let rest = tail.split_off(complete);
+        let text = String::from_utf8_lossy(tail).into_owned();
+        *tail = rest;
+        text
+    }
+}
+
+/// List the recordings of a project, newest first
+pub fn list_recordings(project_dir: &Path) -> io::Result<Vec<RecordingInfo>> {
+    let dir = recordings_dir(project_dir);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut recordings = Vec::new();
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("cast") {
+            continue;
+        }
+
+        // Unreadable files are skipped like ones that are no recording
+        let mut header_line = String::new();
+        let read =
+            File::open(&path).and_then(|file| BufReader::new(file).read_line(&mut header_line));
+        if read.is_err() {
+            continue;
+        }
+        let Ok(header) = serde_json::from_str::<CastHeader>(&header_line) else {
+            continue;
+        };
+
+        recordings.push(RecordingInfo {
+            path: path.to_string_lossy().into_owned(),
+            title: header.title,
+            timestamp: header.timestamp,
+            width: header.width,
+            height: header.height,
+            size_bytes: entry.metadata().map(|m| m.len()).unwrap_or(0),
+        });
+    }
+
+    recordings.sort_by_key(|r| std::cmp::Reverse(r.timestamp));
+    Ok(recordings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_project() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "specflux_rec_{}_{}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_recording_writes_asciicast_v2() {
+        let project = temp_project();
+        let mut recorder = Recorder::create(&project, "task/42", 80, 24).unwrap();
+        let euro = "\u{20AC}".as_bytes();
+        recorder.output(&[b"cost ", &euro[..1]].concat()).unwrap();
+        recorder.output(&euro[1..]).unwrap();
+        recorder.input(b"ls\r").unwrap();
+        recorder.resize(100, 30).unwrap();
+        let path = recorder.finish().unwrap();
+
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("task_42-"));
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["width"], 80);
+        assert_eq!(lines[0]["title"], "task/42");
+        assert_eq!(lines[1][1], "o");
+        assert_eq!(lines[1][2], "cost ");
+        assert_eq!(lines[2][2], "\u{20AC}");
+        assert_eq!(lines[3][1], "i");
+        assert_eq!(lines[4][1], "r");
+        assert_eq!(lines[4][2], "100x30");
+
+        let second = Recorder::create(&project, "task/42", 80, 24).unwrap();
+        let third = Recorder::create(&project, "task/42", 80, 24).unwrap();
+        assert_ne!(second.path(), third.path());
+        second.finish().unwrap();
+        third.finish().unwrap();
+        // Not a file, so it cannot be read
+        fs::create_dir(recordings_dir(&project).join("broken.cast")).unwrap();
+
+        let listed = list_recordings(&project).unwrap();
+        assert_eq!(listed.len(), 3);
+        assert!(listed
+            .iter()
+            .all(|recording| recording.title.as_deref() == Some("task/42")));
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+
+        fs::remove_dir_all(project).unwrap();
+    }
+}
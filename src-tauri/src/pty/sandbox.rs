@@ -0,0 +1,464 @@
+//! Filesystem Sandbox
+//!
+//! Runs a session's command in its own mount and user namespace, so an
+//! agent given broad permissions can only change the task's repositories:
+//! the rest of the filesystem is read-only, `/tmp` is private, and chosen
+//! directories such as `~/.ssh` are hidden. The session daemon's socket and
+//! the user's runtime directory are always hidden so the session cannot ask
+//! something outside the sandbox to run commands for it.
+//!
+//! Uses bubblewrap when it is installed. Otherwise the app binary itself,
+//! started with `SANDBOX_ARG`, sets up the namespaces and execs the
+//! command, which needs unprivileged user namespaces and Linux 5.12.
+//! Under bubblewrap the command also gets a minimal `/dev`, its own pid
+//! namespace and a session without a controlling terminal, so it cannot
+//! push input into the terminal; shells there run without job control.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Command-line flag that starts the binary as the sandbox launcher
+pub const SANDBOX_ARG: &str = "--pty-sandbox";
+
+/// Sandbox settings of a session
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SandboxOptions {
+    /// Directories the session may write to, e.g. the task's repositories;
+    /// the working directory when empty
+    pub writable: Vec<String>,
+    /// Directories replaced by an empty read-only one, e.g. `~/.ssh`;
+    /// writable directories take precedence
+    pub hidden: Vec<String>,
+    /// Cut the session off from the network
+    pub no_network: bool,
+}
+
+/// Mounts of a sandbox, with every path resolved
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Plan {
+    writable: Vec<PathBuf>,
+    hidden: Vec<PathBuf>,
+    network: bool,
+}
+
+impl Plan {
+    fn new(options: &SandboxOptions, cwd: Option<&str>) -> Result<Self, String> {
+        let mut writable = Vec::new();
+        let dirs: Vec<&str> = if options.writable.is_empty() {
+            cwd.into_iter().collect()
+        } else {
+            options.writable.iter().map(String::as_str).collect()
+        };
+        for dir in dirs {
+            let path = Path::new(dir)
+                .canonicalize()
+                .map_err(|e| format!("Invalid writable directory {}: {}", dir, e))?;
+            if !path.is_dir() {
+                return Err(format!("Writable path {} is not a directory", dir));
+            }
+            if path.parent().is_none() {
+                return Err("The root directory cannot be writable in a sandbox".to_string());
+            }
+            writable.push(path);
+        }
+        if writable.is_empty() {
+            return Err("A sandbox needs a working directory or writable directories".to_string());
+        }
+
+        #[cfg(unix)]
+        let runtime = [
+            std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from),
+            Some(super::daemon::runtime_dir()),
+        ];
+        #[cfg(not(unix))]
+        let runtime: [Option<PathBuf>; 0] = [];
+        let hidden = options
+            .hidden
+            .iter()
+            .map(PathBuf::from)
+            .chain(runtime.into_iter().flatten())
+            .filter_map(|path| path.canonicalize().ok())
+            .filter(|path| path.is_dir())
+            .collect();
+
+        Ok(Self {
+            writable,
+            hidden,
+            network: !options.no_network,
+        })
+    }
+
+    /// Arguments making bubblewrap set up the sandbox
+    fn bwrap_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = [
+            "--ro-bind",
+            "/",
+            "/",
+            "--dev",
+            "/dev",
+            "--tmpfs",
+            "/dev/shm",
+            "--tmpfs",
+            "/tmp",
+        ]
+        .map(String::from)
+        .into();
+        for dir in &self.hidden {
+            args.extend([
+                "--perms".to_string(),
+                "0555".to_string(),
+                "--tmpfs".to_string(),
+                dir.to_string_lossy().into_owned(),
+            ]);
+        }
+        for dir in &self.writable {
+            let dir = dir.to_string_lossy().into_owned();
+            args.extend(["--bind".to_string(), dir.clone(), dir]);
+        }
+        if !self.network {
+            args.push("--unshare-net".to_string());
+        }
+        // A session of its own keeps the command from pushing input into
+        // the terminal with TIOCSTI
+        args.extend(["--unshare-pid", "--new-session", "--die-with-parent"].map(String::from));
+        args
+    }
+}
+
+/// Command line running `argv` in a sandbox with working directory `cwd`
+pub fn command(
+    argv: Vec<String>,
+    options: &SandboxOptions,
+    cwd: Option<&str>,
+) -> Result<Vec<String>, String> {
+    if !cfg!(target_os = "linux") {
+        return Err("Sandboxed sessions are only supported on Linux".to_string());
+    }
+    let plan = Plan::new(options, cwd)?;
+
+    let mut command = match find_program("bwrap") {
+        Some(bwrap) => {
+            let mut command = vec![bwrap.to_string_lossy().into_owned()];
+            command.extend(plan.bwrap_args());
+            command
+        }
+        None => {
+            let exe = std::env::current_exe()
+                .map_err(|e| format!("Failed to locate the sandbox launcher: {}", e))?;
+            let plan = serde_json::to_string(&plan)
+                .map_err(|e| format!("Failed to serialize sandbox: {}", e))?;
+            vec![
+                exe.to_string_lossy().into_owned(),
+                SANDBOX_ARG.to_string(),
+                plan,
+            ]
+        }
+    };
+    command.push("--".to_string());
+    command.extend(argv);
+    Ok(command)
+}
+
+/// Look up an executable in `PATH`
+fn find_program(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Run as the sandbox launcher if the process was started with
+/// `SANDBOX_ARG`: enter the sandbox and exec the command.
+///
+/// Returns false when the app should start normally.
+#[cfg(target_os = "linux")]
+pub fn run_if_requested() -> bool {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some(SANDBOX_ARG) {
+        return false;
+    }
+    let error = launch(args);
+    eprintln!("Failed to start sandbox: {}", error);
+    // What shells exit with when a command cannot be run
+    std::process::exit(126);
+}
+
+/// Enter the sandbox described by the arguments and exec the command;
+/// only returns on failure
+#[cfg(target_os = "linux")]
+fn launch(mut args: impl Iterator<Item = String>) -> String {
+    use std::os::unix::process::CommandExt;
+
+    let plan: Plan = match args.next().map(|plan| serde_json::from_str(&plan)) {
+        Some(Ok(plan)) => plan,
+        Some(Err(e)) => return format!("invalid sandbox: {}", e),
+        None => return "missing sandbox".to_string(),
+    };
+    if args.next().as_deref() != Some("--") {
+        return "missing command".to_string();
+    }
+    let argv: Vec<String> = args.collect();
+    let Some((program, args)) = argv.split_first() else {
+        return "missing command".to_string();
+    };
+
+    if let Err(e) = native::enter(&plan) {
+        return e.to_string();
+    }
+    std::process::Command::new(program)
+        .args(args)
+        .exec()
+        .to_string()
+}
+
+/// The sandbox set up without bubblewrap
+#[cfg(target_os = "linux")]
+mod native {
+    use std::ffi::CString;
+    use std::fs::{self, File};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    use super::Plan;
+
+    /// `mount_setattr` flag and attribute, missing from libc
+    const AT_RECURSIVE: libc::c_uint = 0x8000;
+    const MOUNT_ATTR_RDONLY: u64 = 0x1;
+
+    #[repr(C)]
+    struct MountAttr {
+        attr_set: u64,
+        attr_clr: u64,
+        propagation: u64,
+        userns_fd: u64,
+    }
+
+    fn check(rc: libc::c_long) -> io::Result<()> {
+        if rc < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn c_path(path: &Path) -> io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    fn mount(
+        source: &Path,
+        target: &Path,
+        fstype: Option<&str>,
+        flags: libc::c_ulong,
+        data: Option<&str>,
+    ) -> io::Result<()> {
+        let source = c_path(source)?;
+        let target = c_path(target)?;
+        let fstype = fstype.map(|fstype| CString::new(fstype).unwrap());
+        let data = data.map(|data| CString::new(data).unwrap());
+        let rc = unsafe {
+            libc::mount(
+                source.as_ptr(),
+                target.as_ptr(),
+                fstype.as_ref().map_or(std::ptr::null(), |s| s.as_ptr()),
+                flags,
+                data.as_ref()
+                    .map_or(std::ptr::null(), |s| s.as_ptr().cast()),
+            )
+        };
+        check(rc.into()).map_err(|e| {
+            let target = target.to_string_lossy();
+            io::Error::new(e.kind(), format!("mount {}: {}", target, e))
+        })
+    }
+
+    /// Make the mounts at and below `target` read-only, or writable again
+    fn set_readonly(target: &Path, readonly: bool) -> io::Result<()> {
+        let path = c_path(target)?;
+        let attr = MountAttr {
+            attr_set: if readonly { MOUNT_ATTR_RDONLY } else { 0 },
+            attr_clr: if readonly { 0 } else { MOUNT_ATTR_RDONLY },
+            propagation: 0,
+            userns_fd: 0,
+        };
+        let rc = unsafe {
+            libc::syscall(
+                libc::SYS_mount_setattr,
+                libc::AT_FDCWD,
+                path.as_ptr(),
+                AT_RECURSIVE,
+                &attr as *const MountAttr,
+                std::mem::size_of::<MountAttr>(),
+            )
+        };
+        check(rc).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("mount_setattr {}: {}", target.display(), e),
+            )
+        })
+    }
+
+    /// Move the calling, single-threaded process into the sandbox
+    pub fn enter(plan: &Plan) -> io::Result<()> {
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let mut flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS;
+        if !plan.network {
+            flags |= libc::CLONE_NEWNET;
+        }
+        check(unsafe { libc::unshare(flags) }.into())
+            .map_err(|e| io::Error::new(e.kind(), format!("unshare: {}", e)))?;
+        // Keep the user's ids so files stay owned by them
+        for (file, contents) in [
+            ("setgroups", "deny".to_string()),
+            ("uid_map", format!("{} {} 1", uid, uid)),
+            ("gid_map", format!("{} {} 1", gid, gid)),
+        ] {
+            fs::write(format!("/proc/self/{}", file), contents)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", file, e)))?;
+        }
+
+        let root = Path::new("/");
+        let none = Path::new("none");
+        mount(none, root, None, libc::MS_REC | libc::MS_PRIVATE, None)?;
+        // Hold on to the writable directories, which /tmp may cover later;
+        // bind mounts need them from this mount namespace
+        let writable = plan
+            .writable
+            .iter()
+            .map(|dir| {
+                let file = fs::OpenOptions::new()
+                    .read(true)
+                    .custom_flags(libc::O_PATH | libc::O_DIRECTORY)
+                    .open(dir)?;
+                Ok((dir, file))
+            })
+            .collect::<io::Result<Vec<(_, File)>>>()?;
+
+        set_readonly(root, true)?;
+        // Private scratch space, then the hidden directories. Those are only
+        // read-only to the command once it lost its capabilities on exec, so
+        // writable directories inside them can still be mounted.
+        let tmpfs = Path::new("tmpfs");
+        let hidden = plan.hidden.iter().filter(|dir| dir.is_dir());
+        let scratch = [Path::new("/tmp"), Path::new("/dev/shm")];
+        for (dir, mode) in scratch
+            .into_iter()
+            .map(|dir| (dir, "mode=1777"))
+            .chain(hidden.map(|dir| (dir.as_path(), "mode=0555")))
+        {
+            mount(
+                tmpfs,
+                dir,
+                Some("tmpfs"),
+                libc::MS_NOSUID | libc::MS_NODEV,
+                Some(mode),
+            )?;
+        }
+        for (dir, file) in &writable {
+            let _ = fs::create_dir_all(dir);
+            let source = format!("/proc/self/fd/{}", file.as_raw_fd());
+            mount(
+                Path::new(&source),
+                dir,
+                None,
+                libc::MS_BIND | libc::MS_REC,
+                None,
+            )?;
+            set_readonly(dir, false)?;
+        }
+
+        // The working directory still points into the mounts underneath;
+        // where it is gone, e.g. under /tmp, it stays read-only
+        let cwd = std::env::current_dir()?;
+        let _ = std::env::set_current_dir(cwd);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bwrap_args() {
+        let plan = Plan {
+            writable: vec![PathBuf::from("/home/me/repo")],
+            hidden: vec![PathBuf::from("/home/me/.ssh")],
+            network: false,
+        };
+        let args = plan.bwrap_args().join(" ");
+        assert!(args.starts_with("--ro-bind / / --dev /dev"));
+        assert!(args.contains("--bind /home/me/repo /home/me/repo"));
+        assert!(args.contains("--tmpfs /home/me/.ssh"));
+        assert!(args.ends_with("--unshare-net --unshare-pid --new-session --die-with-parent"));
+    }
+
+    #[test]
+    fn test_plan_defaults_to_working_directory() {
+        let cwd = std::env::current_dir().unwrap();
+        let plan = Plan::new(&SandboxOptions::default(), cwd.to_str()).unwrap();
+        assert_eq!(plan.writable, [cwd.canonicalize().unwrap()]);
+        assert!(plan.network);
+
+        assert!(Plan::new(&SandboxOptions::default(), None).is_err());
+        let root = SandboxOptions {
+            writable: vec!["/".into()],
+            ..Default::default()
+        };
+        assert!(Plan::new(&root, None).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_native_sandbox_limits_writes() {
+        use std::os::unix::process::CommandExt;
+
+        // Outside /tmp, which the sandbox replaces
+        let base = std::env::current_exe()
+            .unwrap()
+            .with_file_name(format!("specflux-sandbox-{}", std::process::id()));
+        let repo = base.join("repo");
+        let secret = base.join("secret");
+        for dir in [&repo, &secret] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(secret.join("key"), "x").unwrap();
+        let outside = base.with_extension("probe");
+        let plan = Plan {
+            writable: vec![repo.clone()],
+            hidden: vec![base.clone()],
+            network: false,
+        };
+
+        let mut command = std::process::Command::new("sh");
+        command.arg("-c").arg(format!(
+            "touch repo/ok && ! test -e secret/key && ! touch {} 2>/dev/null \
+             && touch /tmp/private",
+            outside.display()
+        ));
+        command.current_dir(&base);
+        unsafe {
+            command.pre_exec(move || native::enter(&plan));
+        }
+        let status = match command.status() {
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                eprintln!("skipped: no unprivileged user namespaces here");
+                let _ = std::fs::remove_dir_all(&base);
+                return;
+            }
+            status => status.unwrap(),
+        };
+
+        assert!(status.success());
+        assert!(repo.join("ok").exists());
+        assert!(!outside.exists());
+        assert!(!Path::new("/tmp/private").exists());
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
@@ -0,0 +1,189 @@
+//! Headless Terminal Screen
+//!
+//! Feeds session output through a VT100 emulator so the backend knows
+//! what is on screen without a visible xterm.js instance, e.g. for Kanban
+//! thumbnails or for code that inspects what an agent is showing.
+
+use serde::Serialize;
+
+/// Color of a styled cell: a palette index or an RGB triple
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum CellColor {
+    Indexed(u8),
+    Rgb([u8; 3]),
+}
+
+impl CellColor {
+    fn from_vt(color: vt100::Color) -> Option<Self> {
+        match color {
+            vt100::Color::Default => None,
+            vt100::Color::Idx(idx) => Some(Self::Indexed(idx)),
+            vt100::Color::Rgb(r, g, b) => Some(Self::Rgb([r, g, b])),
+        }
+    }
+}
+
+/// A single screen cell with its text and attributes
+#[derive(Debug, Clone, Serialize)]
+pub struct StyledCell {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fg: Option<CellColor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bg: Option<CellColor>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub inverse: bool,
+    pub wide: bool,
+}
+
+/// Cursor location and visibility
+#[derive(Debug, Clone, Serialize)]
+pub struct CursorPosition {
+    pub row: u16,
+    pub col: u16,
+    pub visible: bool,
+}
+
+/// Point-in-time view of a session's visible screen
+#[derive(Debug, Clone, Serialize)]
+pub struct TerminalSnapshot {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub rows: u16,
+    pub cols: u16,
+    pub cursor: CursorPosition,
+    pub title: String,
+    /// Whether a full-screen program switched to the alternate screen
+    #[serde(rename = "alternateScreen")]
+    pub alternate_screen: bool,
+    /// Visible rows as plain text, trailing blanks trimmed
+    pub lines: Vec<String>,
+    /// Visible grid with attributes, when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cells: Option<Vec<Vec<StyledCell>>>,
+}
+
+/// Terminal emulator tracking the visible screen of a session
+pub struct Screen {
+    parser: vt100::Parser,
+}
+
+impl Screen {
+    /// Create an empty screen of the given size
+    pub fn new(rows: u16, cols: u16) -> Self {
+        Self {
+            parser: vt100::Parser::new(rows, cols, 0),
+        }
+    }
+
+    /// Apply output bytes to the screen
+    pub fn process(&mut self, data: &[u8]) {
+        self.parser.process(data);
+    }
+
+    /// Current size as (rows, cols)
+    pub fn size(&self) -> (u16, u16) {
+        self.parser.screen().size()
+    }
+
+    /// Text of the visible screen, one line per row
+    pub fn contents(&self) -> String {
+        self.parser.screen().contents()
+    }
+
+    /// Window title set by the running program
+    pub fn title(&self) -> &str {
+        self.parser.screen().title()
+    }
+
+    /// Change the screen size to match the PTY
+    pub fn resize(&mut self, rows: u16, cols: u16) {
+        self.parser.set_size(rows, cols);
+    }
+
+    /// Capture the visible screen, with per-cell styling if `styled`
+    pub fn snapshot(&self, session_id: &str, styled: bool) -> TerminalSnapshot {
+        let screen = self.parser.screen();
+        let (rows, cols) = screen.size();
+        let (cursor_row, cursor_col) = screen.cursor_position();
+
+        let cells = styled.then(|| {
+            (0..rows)
+                .map(|row| {
+                    (0..cols)
+                        .filter_map(|col| screen.cell(row, col))
+                        .map(|cell| StyledCell {
+                            text: cell.contents(),
+                            fg: CellColor::from_vt(cell.fgcolor()),
+                            bg: CellColor::from_vt(cell.bgcolor()),
+                            bold: cell.bold(),
+                            italic: cell.italic(),
+                            underline: cell.underline(),
+                            inverse: cell.inverse(),
+                            wide: cell.is_wide(),
+                        })
+                        .collect()
+                })
+                .collect()
+        });
+
+        TerminalSnapshot {
+            session_id: session_id.to_string(),
+            rows,
+            cols,
+            cursor: CursorPosition {
+                row: cursor_row,
+                col: cursor_col,
+                visible: !screen.hide_cursor(),
+            },
+            title: screen.title().to_string(),
+            alternate_screen: screen.alternate_screen(),
+            lines: screen.rows(0, cols).collect(),
+            cells,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_plain_text_and_cursor() {
+        let mut screen = Screen::new(4, 20);
+        screen.process(b"\x1b]0;claude\x07hello\r\nworld");
+
+        let snapshot = screen.snapshot("s", false);
+        assert_eq!(snapshot.title, "claude");
+        assert_eq!(snapshot.lines, ["hello", "world", "", ""]);
+        assert_eq!((snapshot.cursor.row, snapshot.cursor.col), (1, 5));
+        assert!(snapshot.cells.is_none());
+    }
+
+    #[test]
+    fn test_snapshot_styled_cells() {
+        let mut screen = Screen::new(2, 10);
+        screen.process(b"\x1b[1;31mE\x1b[0m\x1b[38;2;1;2;3mx");
+
+        let snapshot = screen.snapshot("s", true);
+        let cells = snapshot.cells.unwrap();
+        assert_eq!(cells[0][0].text, "E");
+        assert!(cells[0][0].bold);
+        assert_eq!(cells[0][0].fg, Some(CellColor::Indexed(1)));
+        assert_eq!(cells[0][1].fg, Some(CellColor::Rgb([1, 2, 3])));
+        assert_eq!(cells[0][2].fg, None);
+    }
+
+    #[test]
+    fn test_resize_changes_grid() {
+        let mut screen = Screen::new(24, 80);
+        screen.resize(10, 40);
+
+        let snapshot = screen.snapshot("s", false);
+        assert_eq!((snapshot.rows, snapshot.cols), (10, 40));
+        assert_eq!(snapshot.lines.len(), 10);
+    }
+}
@@ -0,0 +1,375 @@
+//! Scripted Terminal Automation
+//!
+//! Runs expect-style step lists against a session, such as "start claude,
+//! wait for the prompt, send /implement TASK-12, wait until it goes
+//! quiet", and reports what each step did and how long it took.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use super::ansi::AnsiStripper;
+use super::manager::PtyState;
+use super::utf8;
+
+/// Timeout of steps that do not set one
+const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Silence `wait_for_idle` waits for unless a step sets it
+const DEFAULT_QUIET: Duration = Duration::from_secs(2);
+
+/// How often `wait_for_exit` checks the session
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Unmatched output kept for `expect`; older text is dropped
+const MAX_BUFFER_BYTES: usize = 64 * 1024;
+
+/// Output quoted in a failed `expect`
+const FAILURE_CONTEXT_CHARS: usize = 200;
+
+/// One step of a script
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ScriptStep {
+    /// Write text to the session, e.g. `"claude\r"`
+    Send { text: String },
+    /// Wait until the output (escape sequences removed) matches a regex.
+    /// Output consumed by an earlier match is not searched again.
+    #[serde(rename_all = "camelCase")]
+    Expect {
+        pattern: String,
+        timeout_ms: Option<u64>,
+    },
+    /// Wait until the session printed nothing for `quiet_ms`
+    #[serde(rename_all = "camelCase")]
+    WaitForIdle {
+        quiet_ms: Option<u64>,
+        timeout_ms: Option<u64>,
+    },
+    /// Wait until the session's process exits
+    #[serde(rename_all = "camelCase")]
+    WaitForExit { timeout_ms: Option<u64> },
+}
+
+/// What a step did
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepResult {
+    pub index: usize,
+    pub step: ScriptStep,
+    pub ok: bool,
+    /// Milliseconds since the script started
+    pub started_ms: u64,
+    pub duration_ms: u64,
+    /// Text matched by `expect`
+    pub matched: Option<String>,
+    /// Capture groups of the `expect` match
+    pub groups: Vec<Option<String>>,
+    /// How the process ended, for `wait_for_exit`
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub error: Option<String>,
+}
+
+/// Outcome of a script run
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptTranscript {
+    pub success: bool,
+    /// Index of the step that failed; later steps did not run
+    pub failed_step: Option<usize>,
+    pub duration_ms: u64,
+    pub steps: Vec<StepResult>,
+}
+
+#[derive(Default)]
+struct Outcome {
+    matched: Option<String>,
+    groups: Vec<Option<String>>,
+    exit_code: Option<i32>,
+    signal: Option<i32>,
+}
+
+/// Run `steps` in order against a session, stopping at the first failure.
+///
+/// Only fails as a whole if the session does not exist; step failures
+/// are reported in the transcript.
+pub fn run(
+    state: &PtyState,
+    session_id: &str,
+    steps: Vec<ScriptStep>,
+) -> Result<ScriptTranscript, String> {
+    let mut output = OutputBuffer::new(state.tap_session(session_id)?);
+    let start = Instant::now();
+    let mut results = Vec::with_capacity(steps.len());
+    let mut failed_step = None;
+
+    for (index, step) in steps.into_iter().enumerate() {
+        let step_start = Instant::now();
+        let outcome = match &step {
+            ScriptStep::Send { text } => state
+                .write_to_session(session_id, text.as_bytes())
+                .map(|_| Outcome::default()),
+            ScriptStep::Expect {
+                pattern,
+                timeout_ms,
+            } => output.expect(pattern, timeout(*timeout_ms)),
+            ScriptStep::WaitForIdle {
+                quiet_ms,
+                timeout_ms,
+            } => {
+                let quiet = quiet_ms.map_or(DEFAULT_QUIET, Duration::from_millis);
+                output.wait_for_idle(quiet, timeout(*timeout_ms))
+            }
+            ScriptStep::WaitForExit { timeout_ms } => {
+                wait_for_exit(state, session_id, &mut output, timeout(*timeout_ms))
+            }
+        };
+
+        let ok = outcome.is_ok();
+        let (outcome, error) = match outcome {
+            Ok(outcome) => (outcome, None),
+            Err(e) => (Outcome::default(), Some(e)),
+        };
+        results.push(StepResult {
+            index,
+            step,
+            ok,
+            started_ms: millis(step_start - start),
+            duration_ms: millis(step_start.elapsed()),
+            matched: outcome.matched,
+            groups: outcome.groups,
+            exit_code: outcome.exit_code,
+            signal: outcome.signal,
+            error,
+        });
+        if !ok {
+            failed_step = Some(index);
+            break;
+        }
+    }
+
+    Ok(ScriptTranscript {
+        success: failed_step.is_none(),
+        failed_step,
+        duration_ms: millis(start.elapsed()),
+        steps: results,
+    })
+}
+
+fn timeout(timeout_ms: Option<u64>) -> Duration {
+    timeout_ms.map_or(DEFAULT_STEP_TIMEOUT, Duration::from_millis)
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+fn wait_for_exit(
+    state: &PtyState,
+    session_id: &str,
+    output: &mut OutputBuffer,
+    timeout: Duration,
+) -> Result<Outcome, String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(exit) = state.session_exit(session_id)? {
+            return Ok(Outcome {
+                exit_code: exit.exit_code,
+                signal: exit.signal,
+                ..Default::default()
+            });
+        }
+        if Instant::now() >= deadline {
+            return Err(format!("Timed out after {:?} waiting for exit", timeout));
+        }
+        // Keep consuming output so expect after this step sees it in order
+        if !output.pull(EXIT_POLL_INTERVAL) && output.closed {
+            std::thread::sleep(EXIT_POLL_INTERVAL);
+        }
+    }
+}
+
+/// Plain text output of a session not yet consumed by a match
+struct OutputBuffer {
+    chunks: Receiver<Vec<u8>>,
+    stripper: AnsiStripper,
+    /// Stripped bytes ending in a split character
+    partial: Vec<u8>,
+    text: String,
+    last_output: Instant,
+    /// The session dropped its end of the output tap
+    closed: bool,
+}
+
+impl OutputBuffer {
+    fn new(chunks: Receiver<Vec<u8>>) -> Self {
+        Self {
+            chunks,
+            stripper: AnsiStripper::default(),
+            partial: Vec::new(),
+            text: String::new(),
+            last_output: Instant::now(),
+            closed: false,
+        }
+    }
+
+    /// Wait up to `timeout` for output; returns false if none arrived
+    fn pull(&mut self, timeout: Duration) -> bool {
+        let chunk = match self.chunks.recv_timeout(timeout) {
+            Ok(chunk) => chunk,
+            Err(RecvTimeoutError::Timeout) => return false,
+            Err(RecvTimeoutError::Disconnected) => {
+                self.closed = true;
+                return false;
+            }
+        };
+        self.last_output = Instant::now();
+        self.stripper.feed(&chunk, &mut self.partial);
+        let complete = self.partial.len() - utf8::incomplete_tail_len(&self.partial);
+        let tail = self.partial.split_off(complete);
+        self.text.push_str(&String::from_utf8_lossy(&self.partial));
+        self.partial = tail;
+
+        let excess = self.text.len().saturating_sub(MAX_BUFFER_BYTES);
+        if excess > 0 {
+            let cut = (excess..=self.text.len())
+                .find(|&i| self.text.is_char_boundary(i))
+                .unwrap_or(self.text.len());
+            self.text.drain(..cut);
+        }
+        true
+    }
+
+    fn expect(&mut self, pattern: &str, timeout: Duration) -> Result<Outcome, String> {
+        let regex = Regex::new(pattern).map_err(|e| format!("Invalid pattern: {}", e))?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(captures) = regex.captures(&self.text) {
+                let text = |m: regex::Match| m.as_str().to_string();
+                let outcome = Outcome {
+                    matched: captures.get(0).map(text),
+                    groups: captures.iter().skip(1).map(|m| m.map(text)).collect(),
+                    ..Default::default()
+                };
+                let end = captures.get(0).map_or(0, |m| m.end());
+                self.text.drain(..end);
+                return Ok(outcome);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                let skip = self
+                    .text
+                    .chars()
+                    .count()
+                    .saturating_sub(FAILURE_CONTEXT_CHARS);
+                let recent: String = self.text.chars().skip(skip).collect();
+                return Err(format!(
+                    "Timed out after {:?} waiting for /{}/; recent output: {:?}",
+                    timeout, pattern, recent
+                ));
+            }
+            if !self.pull(remaining) && self.closed {
+                return Err(format!("Session closed while waiting for /{}/", pattern));
+            }
+        }
+    }
+
+    fn wait_for_idle(&mut self, quiet: Duration, timeout: Duration) -> Result<Outcome, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let silent = self.last_output.elapsed();
+            if silent >= quiet {
+                return Ok(Outcome::default());
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(format!(
+                    "Timed out after {:?} waiting for {:?} of quiet",
+                    timeout, quiet
+                ));
+            }
+            if !self.pull((quiet - silent).min(remaining)) && self.closed {
+                return Ok(Outcome::default());
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::pty::events::EventEmitter;
+    use crate::pty::session::SpawnOptions;
+
+    fn steps(json: &str) -> Vec<ScriptStep> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn spawn_sh(state: &PtyState, session_id: &str) {
+        let options = SpawnOptions {
+            command: Some("sh".to_string()),
+            ..Default::default()
+        };
+        state
+            .spawn_session(session_id.into(), options, EventEmitter::discard())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_script_sends_expects_and_waits_for_exit() {
+        let state = PtyState::new();
+        spawn_sh(&state, "script-ok");
+
+        let transcript = run(
+            &state,
+            "script-ok",
+            steps(
+                r#"[
+                    {"type": "send", "text": "echo result-$((40 + 2))\n"},
+                    {"type": "expect", "pattern": "result-(\\d+)", "timeoutMs": 5000},
+                    {"type": "waitForIdle", "quietMs": 100},
+                    {"type": "send", "text": "exit 3\n"},
+                    {"type": "waitForExit", "timeoutMs": 5000}
+                ]"#,
+            ),
+        )
+        .unwrap();
+
+        assert!(transcript.success, "{:?}", transcript);
+        assert_eq!(transcript.steps[1].groups, [Some("42".to_string())]);
+        assert_eq!(transcript.steps[4].exit_code, Some(3));
+        let _ = state.close_session("script-ok", Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_script_stops_at_failed_step() {
+        let state = PtyState::new();
+        spawn_sh(&state, "script-fail");
+
+        let transcript = run(
+            &state,
+            "script-fail",
+            steps(
+                r#"[
+                    {"type": "expect", "pattern": "never printed", "timeoutMs": 200},
+                    {"type": "send", "text": "exit\n"}
+                ]"#,
+            ),
+        )
+        .unwrap();
+
+        assert!(!transcript.success);
+        assert_eq!(transcript.failed_step, Some(0));
+        assert_eq!(transcript.steps.len(), 1);
+        assert!(transcript.steps[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .starts_with("Timed out"));
+        assert!(run(&state, "missing", Vec::new()).is_err());
+        let _ = state.close_session("script-fail", Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,118 @@
+//! PTY Scrollback Buffer
+//!
+//! Bounded ring buffer holding the most recent output of a session so a
+//! frontend that reattaches (after a reload or route change) can redraw it.
+
+use std::collections::VecDeque;
+
+/// Default scrollback size per session (1 MiB)
+pub const DEFAULT_SCROLLBACK_BYTES: usize = 1024 * 1024;
+
+/// Ring buffer of raw terminal output with a running byte sequence number.
+///
+/// The sequence number is the total number of bytes ever pushed, so every
+/// output chunk can be located in the stream regardless of what has been
+/// evicted from the buffer.
+pub struct Scrollback {
+    data: VecDeque<u8>,
+    capacity: usize,
+    end_seq: u64,
+}
+
+impl Scrollback {
+    /// Create an empty buffer holding at most `capacity` bytes
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            data: VecDeque::with_capacity(capacity.min(64 * 1024)),
+            capacity,
+            end_seq: 0,
+        }
+    }
+
+    /// Append a chunk, evicting the oldest bytes when full.
+    ///
+    /// Returns the sequence number of the first byte of the chunk.
+    pub fn push(&mut self, chunk: &[u8]) -> u64 {
+        let start_seq = self.end_seq;
+        self.end_seq += chunk.len() as u64;
+
+        if self.capacity == 0 {
+            return start_seq;
+        }
+
+        // Only the tail of an oversized chunk can fit
+        let chunk = if chunk.len() > self.capacity {
+            &chunk[chunk.len() - self.capacity..]
+        } else {
+            chunk
+        };
+
+        let overflow = (self.data.len() + chunk.len()).saturating_sub(self.capacity);
+        self.data.drain(..overflow);
+        self.data.extend(chunk);
+
+        start_seq
+    }
+
+    /// Sequence number of the next byte
+    pub fn end_seq(&self) -> u64 {
+        self.end_seq
+    }
+
+    /// Copy out the buffered bytes and the sequence number of the next byte
+    pub fn snapshot(&self) -> (Vec<u8>, u64) {
+        let (front, back) = self.data.as_slices();
+        let mut data = Vec::with_capacity(self.data.len());
+        data.extend_from_slice(front);
+        data.extend_from_slice(back);
+        (data, self.end_seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_within_capacity() {
+        let mut buf = Scrollback::new(16);
+        assert_eq!(buf.push(b"hello "), 0);
+        assert_eq!(buf.push(b"world"), 6);
+
+        let (data, seq) = buf.snapshot();
+        assert_eq!(data, b"hello world");
+        assert_eq!(seq, 11);
+    }
+
+    #[test]
+    fn test_push_evicts_oldest_bytes() {
+        let mut buf = Scrollback::new(8);
+        buf.push(b"abcdef");
+        assert_eq!(buf.push(b"ghij"), 6);
+
+        let (data, seq) = buf.snapshot();
+        assert_eq!(data, b"cdefghij");
+        assert_eq!(seq, 10);
+    }
+
+    #[test]
+    fn test_oversized_chunk_keeps_tail() {
+        let mut buf = Scrollback::new(4);
+        buf.push(b"xy");
+        buf.push(b"0123456789");
+
+        let (data, seq) = buf.snapshot();
+        assert_eq!(data, b"6789");
+        assert_eq!(seq, 12);
+    }
+
+    #[test]
+    fn test_zero_capacity_tracks_sequence_only() {
+        let mut buf = Scrollback::new(0);
+        buf.push(b"abc");
+
+        let (data, seq) = buf.snapshot();
+        assert!(data.is_empty());
+        assert_eq!(seq, 3);
+    }
+}
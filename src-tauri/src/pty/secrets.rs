@@ -0,0 +1,308 @@
+//! Terminal Secret Store
+//!
+//! Secrets such as API keys kept in the OS keychain (Keychain on macOS,
+//! Credential Manager on Windows, the Secret Service on Linux). Values are
+//! entered in a native prompt and sessions reference them by name, so they
+//! never pass through the webview. The keychain cannot list entries, so the
+//! names are kept in a file in the app data directory.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use keyring::Entry;
+
+use super::env::SecretVar;
+
+/// File in the app data directory holding the names of the secrets
+const SECRETS_FILE: &str = "terminal-secrets.json";
+
+/// Keychain service the secrets are stored under
+const KEYCHAIN_SERVICE: &str = "specflux-terminal";
+
+/// Path of the secret store in `data_dir`
+pub fn secrets_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(SECRETS_FILE)
+}
+
+fn entry(name: &str) -> Result<Entry, String> {
+    Entry::new(KEYCHAIN_SERVICE, name).map_err(|e| format!("Failed to open keychain: {}", e))
+}
+
+/// Names in the store. A store written before secrets moved to the
+/// keychain maps names to values; those are moved to the keychain first.
+fn load(path: &Path) -> Result<Vec<String>, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read secret store: {}", e)),
+    };
+    if let Ok(plaintext) = serde_json::from_str::<BTreeMap<String, String>>(&contents) {
+        for (name, value) in &plaintext {
+            entry(name)?
+                .set_password(value)
+                .map_err(|e| format!("Failed to store secret {}: {}", name, e))?;
+        }
+        let names: Vec<String> = plaintext.into_keys().collect();
+        store(path, &names)?;
+        return Ok(names);
+    }
+    serde_json::from_str(&contents).map_err(|e| format!("Invalid secret store: {}", e))
+}
+
+fn store(path: &Path, names: &[String]) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    }
+    let json = serde_json::to_string_pretty(names)
+        .map_err(|e| format!("Failed to serialize secret store: {}", e))?;
+
+    let partial = path.with_extension("json.tmp");
+    let _ = fs::remove_file(&partial);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&partial)
+        .and_then(|mut file| file.write_all(json.as_bytes()))
+        .and_then(|_| fs::rename(&partial, path))
+        .map_err(|e| format!("Failed to write secret store: {}", e))
+}
+
+/// Names of the stored secrets
+pub fn names(path: &Path) -> Result<Vec<String>, String> {
+    load(path)
+}
+
+/// Store a secret, replacing one with the same name
+pub fn set(path: &Path, name: &str, value: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Secret names must not be empty".to_string());
+    }
+    let mut names = load(path)?;
+    entry(name)?
+        .set_password(value)
+        .map_err(|e| format!("Failed to store secret {}: {}", name, e))?;
+    if let Err(at) = names.binary_search_by(|other| other.as_str().cmp(name)) {
+        names.insert(at, name.to_string());
+        store(path, &names)?;
+    }
+    Ok(())
+}
+
+/// Delete a secret; returns false if there was none with this name
+pub fn remove(path: &Path, name: &str) -> Result<bool, String> {
+    let mut names = load(path)?;
+    let Some(at) = names.iter().position(|other| other == name) else {
+        return Ok(false);
+    };
+    match entry(name)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(e) => return Err(format!("Failed to delete secret {}: {}", name, e)),
+    }
+    names.remove(at);
+    store(path, &names)?;
+    Ok(true)
+}
+
+/// Look up the secrets for `(variable, secret name)` pairs
+pub fn resolve(
+    path: &Path,
+    refs: impl IntoIterator<Item = (String, String)>,
+) -> Result<Vec<SecretVar>, String> {
+    let refs: Vec<_> = refs.into_iter().collect();
+    if !refs.is_empty() {
+        // Moves a plaintext store to the keychain before reading from it
+        load(path)?;
+    }
+    refs.into_iter()
+        .map(|(variable, secret)| {
+            let value = match entry(&secret)?.get_password() {
+                Ok(value) => value,
+                Err(keyring::Error::NoEntry) => return Err(format!("Secret {} not found", secret)),
+                Err(e) => return Err(format!("Failed to read secret {}: {}", secret, e)),
+            };
+            Ok(SecretVar {
+                variable,
+                secret,
+                value,
+            })
+        })
+        .collect()
+}
+
+/// Ask the user for the value of secret `name` in a native password
+/// dialog; None if the dialog was cancelled. Blocks until it is closed.
+pub fn prompt(name: &str) -> Result<Option<String>, String> {
+    let title = format!("Value of secret {}", name);
+    let output = prompt_command(&title)?
+        .output()
+        .map_err(|e| format!("Failed to show secret prompt: {}", e))?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let value = String::from_utf8(output.stdout)
+        .map_err(|_| "Secret values must be valid UTF-8".to_string())?;
+    let value = value.strip_suffix('\n').unwrap_or(&value);
+    Ok(Some(value.strip_suffix('\r').unwrap_or(value).to_string()))
+}
+
+#[cfg(target_os = "macos")]
+fn prompt_command(title: &str) -> Result<Command, String> {
+    let mut command = Command::new("osascript");
+    command.args([
+        "-e",
+        "on run argv",
+        "-e",
+        "display dialog (item 1 of argv) default answer \"\" with hidden answer",
+        "-e",
+        "return text returned of result",
+        "-e",
+        "end run",
+        title,
+    ]);
+    Ok(command)
+}
+
+#[cfg(windows)]
+fn prompt_command(title: &str) -> Result<Command, String> {
+    let mut command = Command::new("powershell");
+    command
+        .args([
+            "-NoProfile",
+            "-Command",
+            "$c = Get-Credential -UserName specflux -Message $env:SPECFLUX_SECRET_PROMPT; \
+             if (-not $c) { exit 1 }; $c.GetNetworkCredential().Password",
+        ])
+        .env("SPECFLUX_SECRET_PROMPT", title);
+    Ok(command)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn prompt_command(title: &str) -> Result<Command, String> {
+    let on_path = |program: &str| {
+        std::env::var_os("PATH")
+            .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+    };
+    if on_path("zenity") {
+        let mut command = Command::new("zenity");
+        command.args(["--password", "--title", title]);
+        Ok(command)
+    } else if on_path("kdialog") {
+        let mut command = Command::new("kdialog");
+        command.args(["--password", title, "--title", title]);
+        Ok(command)
+    } else {
+        Err("Entering secrets needs zenity or kdialog".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keyring::credential::{Credential, CredentialApi, CredentialBuilderApi};
+    use std::any::Any;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// Keychain kept in memory, shared by all entries
+    #[derive(Debug, Default)]
+    struct MemoryKeychain(Arc<Mutex<HashMap<String, Vec<u8>>>>);
+
+    #[derive(Debug)]
+    struct MemoryEntry {
+        keychain: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+        user: String,
+    }
+
+    impl CredentialApi for MemoryEntry {
+        fn set_secret(&self, secret: &[u8]) -> keyring::Result<()> {
+            let mut keychain = self.keychain.lock().unwrap();
+            keychain.insert(self.user.clone(), secret.to_vec());
+            Ok(())
+        }
+
+        fn get_secret(&self) -> keyring::Result<Vec<u8>> {
+            let keychain = self.keychain.lock().unwrap();
+            keychain
+                .get(&self.user)
+                .cloned()
+                .ok_or(keyring::Error::NoEntry)
+        }
+
+        fn delete_credential(&self) -> keyring::Result<()> {
+            let mut keychain = self.keychain.lock().unwrap();
+            keychain
+                .remove(&self.user)
+                .map(|_| ())
+                .ok_or(keyring::Error::NoEntry)
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    impl CredentialBuilderApi for MemoryKeychain {
+        fn build(
+            &self,
+            _target: Option<&str>,
+            _service: &str,
+            user: &str,
+        ) -> keyring::Result<Box<Credential>> {
+            Ok(Box::new(MemoryEntry {
+                keychain: self.0.clone(),
+                user: user.to_string(),
+            }))
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    fn test_store_and_resolve_secrets() {
+        keyring::set_default_credential_builder(Box::new(MemoryKeychain::default()));
+        let dir = std::env::temp_dir().join(format!("specflux-secrets-{}", std::process::id()));
+        let path = secrets_path(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, r#"{"legacy": "sk-0"}"#).unwrap();
+
+        set(&path, "specflux", "sk-1").unwrap();
+        set(&path, "github", "ghp_2").unwrap();
+        set(&path, "specflux", "sk-3").unwrap();
+        assert_eq!(names(&path).unwrap(), ["github", "legacy", "specflux"]);
+        let stored = fs::read_to_string(&path).unwrap();
+        assert!(!stored.contains("sk-"), "{}", stored);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let resolved = resolve(
+            &path,
+            [
+                ("SPECFLUX_API_KEY".into(), "specflux".into()),
+                ("LEGACY".into(), "legacy".into()),
+            ],
+        )
+        .unwrap();
+        assert_eq!(resolved[0].value, "sk-3");
+        assert_eq!(resolved[1].value, "sk-0");
+        assert!(resolve(&path, [("X".into(), "missing".into())]).is_err());
+
+        assert!(remove(&path, "github").unwrap());
+        assert!(!remove(&path, "github").unwrap());
+        assert!(resolve(&path, [("X".into(), "github".into())]).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
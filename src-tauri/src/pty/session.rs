@@ -5,10 +5,73 @@
 
 use parking_lot::Mutex;
 use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
 use std::sync::Arc;
 use std::thread;
-use tauri::{AppHandle, Emitter};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::agent::AgentState;
+use super::child::{self, ChildExit};
+use super::cwd::CwdTracker;
+#[cfg(unix)]
+use super::daemon::{
+    self, client,
+    protocol::{HostedSessionInfo, SpawnRequest},
+};
+use super::env::{self, EnvAudit, EnvPolicy, Environment, SecretVar};
+use super::events::EventEmitter;
+use super::flow::{self, FlowControl, OutputOptions};
+use super::foreground::{self, ForegroundProcess};
+use super::history::CommandRecord;
+use super::info::{self, TerminalSessionInfo};
+use super::limits::{ExceededLimit, ResourceLimits, SessionLimits};
+use super::osc::OscScanner;
+use super::output::{OutputChannel, OutputStream};
+use super::profiles::ShellProfile;
+use super::redact::{RedactionOptions, Redactor};
+use super::sandbox::{self, SandboxOptions};
+use super::screen::{Screen, TerminalSnapshot};
+use super::scrollback::DEFAULT_SCROLLBACK_BYTES;
+#[cfg(unix)]
+use super::secrets;
+use super::stats::{ResourceStats, StatsLimits, StatsResource, StatsSampler};
+use super::timeouts::{SessionTimeouts, TimeoutReason, TimeoutTracker};
+use super::watch::WatchMatch;
+
+/// Initial PTY size until the frontend sends a resize
+const INITIAL_ROWS: u16 = 24;
+const INITIAL_COLS: u16 = 80;
+
+/// Number of raw reads queued for delivery before the reader blocks
+const READ_QUEUE_CHUNKS: usize = 16;
+
+/// How long the exit event waits for the reader to drain remaining output
+const EXIT_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Default time each close signal (SIGHUP, SIGTERM) gets before escalating
+pub const DEFAULT_CLOSE_GRACE: Duration = Duration::from_millis(500);
+
+/// How long close waits for the reader and waiter threads to finish
+const THREAD_JOIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often the monitor thread samples the foreground process, working
+/// directory and agent state, and settles output watchers
+const MONITOR_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often the monitor thread samples resource usage
+const STATS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a profile's initial command waits for the shell's first prompt
+/// before it is typed anyway
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Silence after output taken as the shell waiting at its prompt, for
+/// shells that emit no prompt marks
+const PROMPT_QUIET: Duration = Duration::from_millis(300);
 
 /// Payload for terminal output events
 #[derive(Clone, serde::Serialize)]
@@ -16,6 +79,53 @@ pub struct TerminalOutputPayload {
     #[serde(rename = "sessionId")]
     pub session_id: String,
     pub data: Vec<u8>,
+    /// Stream offset of the first byte in `data`
+    pub seq: u64,
+}
+
+/// Result of attaching to an existing session
+#[derive(Clone, serde::Serialize)]
+pub struct TerminalAttachPayload {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    /// Buffered scrollback, oldest byte first
+    pub data: Vec<u8>,
+    /// Stream offset of the next byte; output events with a lower `seq`
+    /// are already contained in `data`
+    pub seq: u64,
+}
+
+/// Payload for working directory change events
+#[derive(Clone, serde::Serialize)]
+pub struct TerminalCwdPayload {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub cwd: String,
+}
+
+/// Payload for output watcher match events
+#[derive(Clone, serde::Serialize)]
+pub struct TerminalMatchPayload {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    #[serde(flatten)]
+    pub watch_match: WatchMatch,
+}
+
+/// Payload for agent state change events
+#[derive(Clone, serde::Serialize)]
+pub struct TerminalAgentStatePayload {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub state: AgentState,
+}
+
+/// Payload for foreground process change events
+#[derive(Clone, serde::Serialize)]
+pub struct TerminalForegroundPayload {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub process: ForegroundProcess,
 }
 
 /// Payload for terminal exit events
@@ -25,112 +135,760 @@ pub struct TerminalExitPayload {
     pub session_id: String,
     #[serde(rename = "exitCode")]
     pub exit_code: Option<i32>,
+    /// Signal number that terminated the process, if any
+    pub signal: Option<i32>,
+    /// Resource limit the process was killed for exceeding
+    pub limit: Option<ExceededLimit>,
+}
+
+/// Payload for events announcing a session went quiet
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalIdlePayload {
+    pub session_id: String,
+    /// How long the session has printed nothing
+    pub idle_ms: u64,
+    /// Unix time in milliseconds of the last output
+    pub last_output_at: u64,
+}
+
+/// Payload for events announcing a session is closed by a timeout
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalTimeoutPayload {
+    pub session_id: String,
+    pub reason: TimeoutReason,
+}
+
+/// Payload for periodic resource usage events
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalStatsPayload {
+    pub session_id: String,
+    pub stats: ResourceStats,
+}
+
+/// Payload for events announcing a session crossed a usage limit
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalStatsWarningPayload {
+    pub session_id: String,
+    pub resource: StatsResource,
+    pub value: f64,
+    pub limit: f64,
+}
+
+/// Options for spawning a PTY session
+#[derive(Default)]
+pub struct SpawnOptions {
+    /// Working directory for the process
+    pub cwd: Option<String>,
+    /// Additional environment variables
+    pub env: Option<HashMap<String, String>>,
+    /// Scrollback buffer size, defaults to `DEFAULT_SCROLLBACK_BYTES`
+    pub scrollback_bytes: Option<usize>,
+    /// Program to run instead of the user's shell
+    pub command: Option<String>,
+    /// Arguments for `command`
+    pub args: Vec<String>,
+    /// Run as (or through) a login shell; defaults to true only for the shell
+    pub login: Option<bool>,
+    /// Run as (or through) an interactive shell; defaults to true only for the shell
+    pub interactive: Option<bool>,
+    /// Stream output as raw frames to this channel instead of broadcast events
+    pub output_channel: Option<OutputChannel>,
+    /// Output coalescing and flow-control settings
+    pub output_options: OutputOptions,
+    /// Host the PTY in the session daemon so it survives an app restart
+    /// (Unix only)
+    pub persistent: bool,
+    /// User-supplied labels, e.g. the task the session belongs to
+    pub labels: HashMap<String, String>,
+    /// Start bash or zsh with OSC 133 prompt marks so the commands run in
+    /// the session are recorded (Unix only)
+    pub shell_integration: bool,
+    /// Shell to start instead of `$SHELL -l -i`; cannot be combined with
+    /// `command`
+    pub profile: Option<ShellProfile>,
+    /// Which of the app's environment variables the process inherits
+    pub env_policy: EnvPolicy,
+    /// Secrets set as environment variables, overriding `env`
+    pub secrets: Vec<SecretVar>,
+    /// Which secrets are masked in the output; the values of `secrets`
+    /// always are unless redaction is disabled
+    pub redaction: RedactionOptions,
+    /// When to announce the session as idle and when to close it
+    pub timeouts: SessionTimeouts,
+    /// Usage that triggers `terminal-stats-warning`
+    pub stats_limits: StatsLimits,
+    /// What the session's processes may consume (Linux only)
+    pub resource_limits: ResourceLimits,
+    /// Run the process in a filesystem sandbox (Linux only)
+    pub sandbox: Option<SandboxOptions>,
+}
+
+/// Where the PTY of a session lives
+enum Host {
+    /// Owned by this process; closed with the app
+    Local {
+        master: Mutex<Box<dyn MasterPty + Send>>,
+        #[cfg(not(unix))]
+        killer: Mutex<Box<dyn portable_pty::ChildKiller + Send + Sync>>,
+    },
+    /// Owned by the session daemon; only the connection closes with the app
+    #[cfg(unix)]
+    Daemon(Arc<client::Connection>),
+}
+
+impl Host {
+    /// Foreground process group of the PTY led by `pid`
+    fn foreground_pgrp(&self, pid: Option<u32>) -> Option<i32> {
+        match self {
+            #[cfg(unix)]
+            Host::Local { master } => {
+                let _ = pid;
+                master.lock().process_group_leader()
+            }
+            #[cfg(not(unix))]
+            Host::Local { .. } => {
+                let _ = pid;
+                None
+            }
+            // The master lives in the daemon, but /proc knows the answer too
+            #[cfg(target_os = "linux")]
+            Host::Daemon(_) => super::procfs::read_stat(pid? as i32)
+                .map(|stat| stat.tpgid)
+                .filter(|&pgrp| pgrp > 0),
+            #[cfg(all(unix, not(target_os = "linux")))]
+            Host::Daemon(_) => {
+                let _ = pid;
+                None
+            }
+        }
+    }
+}
+
+/// A PTY and its process, ready to be wired to the output pipeline
+struct Pty {
+    host: Host,
+    pid: Option<u32>,
+    command: Vec<String>,
+    cwd: Option<String>,
+    created_at: SystemTime,
+    rows: u16,
+    cols: u16,
+    reader: Box<dyn Read + Send>,
+    writer: Box<dyn Write + Send>,
+    /// Blocks until the process exits; `None` if that will not be observed
+    wait: Box<dyn FnOnce() -> Option<ChildExit> + Send>,
 }
 
 /// A single PTY session
-#[allow(dead_code)]
 pub struct PtySession {
-    session_id: String, // Kept for potential debugging use
+    session_id: String,
     writer: Arc<Mutex<Box<dyn Write + Send>>>,
-    master: Arc<Mutex<Box<dyn MasterPty + Send>>>,
-    _reader_handle: thread::JoinHandle<()>,
+    host: Arc<Host>,
+    output: Arc<Mutex<OutputStream>>,
+    flow: Arc<FlowControl>,
+    pid: Option<u32>,
+    command: Vec<String>,
+    created_at: SystemTime,
+    labels: HashMap<String, String>,
+    exit: Arc<Mutex<Option<ChildExit>>>,
+    foreground: Arc<Mutex<Option<ForegroundProcess>>>,
+    reader_handle: Mutex<Option<thread::JoinHandle<()>>>,
+    waiter_handle: Mutex<Option<thread::JoinHandle<()>>>,
+    monitor_handle: Mutex<Option<thread::JoinHandle<()>>>,
+    /// Dropped to stop the monitor thread
+    monitor_stop: Mutex<Option<mpsc::Sender<()>>>,
     running: Arc<std::sync::atomic::AtomicBool>,
+    env_audit: EnvAudit,
+    timeouts: Mutex<TimeoutTracker>,
+    stats: Arc<Mutex<StatsSampler>>,
+    limits: Arc<SessionLimits>,
+    events: EventEmitter,
 }
 
 impl PtySession {
-    /// Spawn a new PTY session with the given shell
+    /// Spawn a new PTY session running the user's shell or `options.command`
     pub fn spawn(
         session_id: String,
-        cwd: Option<String>,
-        env: Option<std::collections::HashMap<String, String>>,
-        app: AppHandle,
+        options: SpawnOptions,
+        events: EventEmitter,
     ) -> Result<Self, String> {
+        Self::validate(&options)?;
+        let redactor = Redactor::new(&options.redaction, &options.secrets)?;
+        if options.persistent {
+            #[cfg(unix)]
+            return Self::spawn_hosted(session_id, options, redactor, events);
+            #[cfg(not(unix))]
+            return Err("Persistent sessions are only supported on Unix".to_string());
+        }
+
         let pty_system = native_pty_system();
 
         // Create PTY with initial size
         let pair = pty_system
             .openpty(PtySize {
-                rows: 24,
-                cols: 80,
+                rows: INITIAL_ROWS,
+                cols: INITIAL_COLS,
                 pixel_width: 0,
                 pixel_height: 0,
             })
             .map_err(|e| format!("Failed to open PTY: {}", e))?;
 
-        // Build shell command
-        let shell = std::env::var("SHELL").unwrap_or_else(|_| {
-            if cfg!(windows) {
-                "cmd.exe".to_string()
-            } else {
-                "/bin/bash".to_string()
-            }
-        });
+        // Get writer and reader from master first, so a failure leaves no
+        // child behind
+        let writer = pair
+            .master
+            .take_writer()
+            .map_err(|e| format!("Failed to get writer: {}", e))?;
 
-        let mut cmd = CommandBuilder::new(&shell);
-        // Start as interactive login shell to properly source all config files
-        // -l: login shell (sources .zprofile, .zlogin)
-        // -i: interactive shell (sources .zshrc where most config lives)
-        cmd.arg("-l");
-        cmd.arg("-i");
+        let reader = pair
+            .master
+            .try_clone_reader()
+            .map_err(|e| format!("Failed to clone reader: {}", e))?;
+
+        let (mut cmd, integration_env) = Self::build_command(&options);
+        let command = Self::command_line(&cmd);
+        let (limits, argv) = Self::launch_command(&session_id, &options, &command)?;
+        if argv != command {
+            cmd = CommandBuilder::from_argv(argv.into_iter().map(OsString::from).collect());
+        }
 
         // Set working directory
-        if let Some(dir) = cwd {
+        if let Some(ref dir) = options.cwd {
             cmd.cwd(dir);
         }
 
         // Set environment variables
-        if let Some(ref env_vars) = env {
-            for (key, value) in env_vars {
-                cmd.env(key, value);
-            }
+        let env = Self::environment(&options, integration_env);
+        if env.clear {
+            cmd.env_clear();
+        }
+        for (key, value) in env.vars {
+            cmd.env(key, value);
         }
-
-        // Set TERM for proper terminal emulation
-        cmd.env("TERM", "xterm-256color");
 
         // Spawn the shell process
-        let _child = pair
-            .slave
-            .spawn_command(cmd)
-            .map_err(|e| format!("Failed to spawn shell: {}", e))?;
+        let mut child = match pair.slave.spawn_command(cmd) {
+            Ok(child) => child,
+            Err(e) => {
+                limits.release();
+                return Err(format!("Failed to spawn process: {}", e));
+            }
+        };
+        let pid = child.process_id();
+        #[cfg(not(unix))]
+        let killer = Mutex::new(child.clone_killer());
 
-        // Get writer and reader from master
-        let writer = pair
-            .master
-            .take_writer()
-            .map_err(|e| format!("Failed to get writer: {}", e))?;
+        let pty = Pty {
+            host: Host::Local {
+                master: Mutex::new(pair.master),
+                #[cfg(not(unix))]
+                killer,
+            },
+            pid,
+            command,
+            cwd: options.cwd.clone(),
+            created_at: SystemTime::now(),
+            rows: INITIAL_ROWS,
+            cols: INITIAL_COLS,
+            reader,
+            writer,
+            wait: Box::new(move || Some(child::wait(&mut child))),
+        };
+        Ok(
+            Self::start(session_id, pty, options, limits, redactor, events)
+                .with_env_audit(env.audit),
+        )
+    }
 
-        let reader = pair
-            .master
-            .try_clone_reader()
-            .map_err(|e| format!("Failed to clone reader: {}", e))?;
+    /// Spawn the session process in the session daemon
+    #[cfg(unix)]
+    fn spawn_hosted(
+        session_id: String,
+        options: SpawnOptions,
+        redactor: Option<Redactor>,
+        events: EventEmitter,
+    ) -> Result<Self, String> {
+        let (cmd, integration_env) = Self::build_command(&options);
+        let command = Self::command_line(&cmd);
+        let (limits, argv) = Self::launch_command(&session_id, &options, &command)?;
+        let Environment { clear, vars, audit } = Self::environment(&options, integration_env);
+
+        let created_at = SystemTime::now();
+        let request = SpawnRequest {
+            session_id: session_id.clone(),
+            argv,
+            cwd: options.cwd.clone(),
+            env: vars,
+            clear_env: clear,
+            cols: INITIAL_COLS,
+            rows: INITIAL_ROWS,
+            scrollback_bytes: options.scrollback_bytes.unwrap_or(DEFAULT_SCROLLBACK_BYTES),
+            labels: options.labels.clone(),
+            redaction: options.redaction.clone(),
+            secrets: options
+                .secrets
+                .iter()
+                .map(|secret| (secret.variable.clone(), secret.secret.clone()))
+                .collect(),
+            timeouts: options.timeouts,
+            stats_limits: options.stats_limits,
+            resource_limits: limits.limits(),
+            created_at: info::unix_millis(created_at),
+        };
+        let attachment = match client::spawn(&daemon::socket_path(), request) {
+            Ok(attachment) => attachment,
+            Err(e) => {
+                limits.release();
+                return Err(e);
+            }
+        };
+        let pty = Self::hosted(attachment, command, options.cwd.clone(), created_at);
+        Ok(Self::start(session_id, pty, options, limits, redactor, events).with_env_audit(audit))
+    }
 
-        let master = Arc::new(Mutex::new(pair.master));
+    /// Reconnect to a session left running in the session daemon, e.g.
+    /// after an app restart. Its scrollback is replayed as output, masked
+    /// like the session's output was, with the values of its secrets looked
+    /// up again in the store at `secrets_path`.
+    #[cfg(unix)]
+    pub fn reattach(
+        hosted: HostedSessionInfo,
+        secrets_path: &Path,
+        events: EventEmitter,
+    ) -> Result<Self, String> {
+        let secrets = secrets::resolve(secrets_path, hosted.secrets)?;
+        let redactor = Redactor::new(&hosted.redaction, &secrets)?;
+        let attachment = client::attach(&daemon::socket_path(), &hosted.session_id)?;
+        let created_at = UNIX_EPOCH + Duration::from_millis(hosted.created_at);
+        let pty = Self::hosted(attachment, hosted.argv, hosted.cwd, created_at);
+        let options = SpawnOptions {
+            persistent: true,
+            labels: hosted.labels,
+            timeouts: hosted.timeouts,
+            stats_limits: hosted.stats_limits,
+            resource_limits: hosted.resource_limits,
+            ..Default::default()
+        };
+        let limits = SessionLimits::reattach(&hosted.session_id, hosted.resource_limits);
+        Ok(Self::start(
+            hosted.session_id,
+            pty,
+            options,
+            limits,
+            redactor,
+            events,
+        ))
+    }
+
+    #[cfg(unix)]
+    fn hosted(
+        attachment: client::Attachment,
+        command: Vec<String>,
+        cwd: Option<String>,
+        created_at: SystemTime,
+    ) -> Pty {
+        let client::Attachment {
+            connection,
+            output,
+            exit,
+        } = attachment;
+        let (rows, cols) = connection.size();
+        Pty {
+            host: Host::Daemon(connection.clone()),
+            pid: connection.pid(),
+            command,
+            cwd,
+            created_at,
+            rows,
+            cols,
+            reader: Box::new(output),
+            writer: Box::new(client::InputWriter(connection)),
+            wait: Box::new(move || exit.recv().ok()),
+        }
+    }
+
+    /// Wire a PTY to the output pipeline and exit reporting
+    fn start(
+        session_id: String,
+        pty: Pty,
+        options: SpawnOptions,
+        limits: SessionLimits,
+        redactor: Option<Redactor>,
+        events: EventEmitter,
+    ) -> Self {
+        let Pty {
+            host,
+            pid,
+            command,
+            cwd,
+            created_at,
+            rows,
+            cols,
+            reader,
+            writer,
+            wait,
+        } = pty;
+
+        let marks = Self::emits_prompt_marks(&options);
         let writer = Arc::new(Mutex::new(writer));
         let running = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let scrollback_bytes = options.scrollback_bytes.unwrap_or(DEFAULT_SCROLLBACK_BYTES);
+        let mut output = OutputStream::new(
+            session_id.clone(),
+            scrollback_bytes,
+            Screen::new(rows, cols),
+            options.output_channel,
+            events.clone(),
+        );
+        output.set_cwd_tracker(CwdTracker::new(pid, cwd));
+        output.set_redactor(redactor);
+        // Tapped before any output arrives so the first prompt is not missed
+        let initial_command = options
+            .profile
+            .as_ref()
+            .and_then(|profile| profile.initial_command.clone())
+            .map(|command| (command, output.tap()));
+        let output = Arc::new(Mutex::new(output));
 
-        // Spawn reader thread to stream output to frontend
-        let session_id_clone = session_id.clone();
+        let flow = Arc::new(FlowControl::new(options.output_options.flow_control_window));
+
+        // Spawn reader thread to stream output to frontend. It hands raw
+        // reads to a delivery thread that batches them, and blocks when
+        // flow control pauses delivery.
+        let (reader_done_tx, reader_done_rx) = mpsc::channel::<()>();
         let running_clone = running.clone();
+        let output_clone = output.clone();
+        let flow_clone = flow.clone();
+        let output_options = options.output_options;
         let reader_handle = thread::spawn(move || {
-            Self::read_output(reader, session_id_clone, running_clone, app);
+            let (chunk_tx, chunk_rx) = mpsc::sync_channel(READ_QUEUE_CHUNKS);
+            let pump_handle = thread::spawn(move || {
+                flow::pump(chunk_rx, &output_clone, &flow_clone, &output_options);
+            });
+            Self::read_output(reader, running_clone, chunk_tx);
+            let _ = pump_handle.join();
+            drop(reader_done_tx);
+        });
+
+        // Type the profile's initial command once the shell is ready for it
+        if let Some((command, tapped)) = initial_command {
+            let writer = writer.clone();
+            let output = output.clone();
+            let running = running.clone();
+            thread::spawn(move || {
+                Self::wait_for_prompt(tapped, marks);
+                if running.load(std::sync::atomic::Ordering::Relaxed) {
+                    let input = format!("{}\r", command);
+                    let _ = Self::write_input(&writer, &output, input.as_bytes());
+                }
+            });
+        }
+
+        // Spawn waiter thread to reap the child and report its exit status
+        let session_id_clone = session_id.clone();
+        let exit_status = Arc::new(Mutex::new(None));
+        let exit_clone = exit_status.clone();
+        let events_clone = events.clone();
+        let output_clone = output.clone();
+        let limits = Arc::new(limits);
+        let limits_clone = limits.clone();
+        let waiter_handle = thread::spawn(move || {
+            let exit = wait();
+            // Let the reader flush output written just before exit
+            let _ = reader_done_rx.recv_timeout(EXIT_DRAIN_TIMEOUT);
+            if let Some(exit) = exit {
+                *exit_clone.lock() = Some(exit);
+                output_clone.lock().update_agent(None, true);
+                let limit = limits_clone.exceeded(exit);
+                Self::emit_exit(&events_clone, session_id_clone, exit, limit);
+            }
         });
 
-        Ok(Self {
+        // Spawn monitor thread to follow the foreground process, working
+        // directory, agent state and resource usage, and report watcher
+        // matches held back until output goes quiet
+        let host = Arc::new(host);
+        let foreground = Arc::new(Mutex::new(None));
+        let stats = Arc::new(Mutex::new(StatsSampler::new(pid, options.stats_limits)));
+        let (monitor_stop, monitor_stop_rx) = mpsc::channel::<()>();
+        let monitor_handle = {
+            let session_id = session_id.clone();
+            let host = host.clone();
+            let exit = exit_status.clone();
+            let foreground = foreground.clone();
+            let output = output.clone();
+            let stats = stats.clone();
+            let events = events.clone();
+            thread::spawn(move || {
+                let mut last_stats = Instant::now();
+                while monitor_stop_rx.recv_timeout(MONITOR_INTERVAL)
+                    == Err(mpsc::RecvTimeoutError::Timeout)
+                    && exit.lock().is_none()
+                {
+                    Self::sample_foreground(&session_id, &host, pid, &foreground, &events);
+                    let process = foreground.lock().clone();
+                    {
+                        let mut output = output.lock();
+                        output.poll_cwd();
+                        output.update_agent(process.as_ref(), false);
+                        output.settle_watchers();
+                    }
+                    if last_stats.elapsed() >= STATS_INTERVAL {
+                        last_stats = Instant::now();
+                        Self::sample_stats(&session_id, &stats, &events);
+                    }
+                }
+            })
+        };
+
+        Self {
             session_id,
             writer,
-            master,
-            _reader_handle: reader_handle,
+            host,
+            output,
+            flow,
+            pid,
+            command,
+            created_at,
+            labels: options.labels,
+            exit: exit_status,
+            foreground,
+            reader_handle: Mutex::new(Some(reader_handle)),
+            waiter_handle: Mutex::new(Some(waiter_handle)),
+            monitor_handle: Mutex::new(Some(monitor_handle)),
+            monitor_stop: Mutex::new(Some(monitor_stop)),
             running,
-        })
+            env_audit: EnvAudit::default(),
+            timeouts: Mutex::new(TimeoutTracker::new(options.timeouts)),
+            stats,
+            limits,
+            events,
+        }
     }
 
-    /// Read output from PTY and emit events to frontend
+    /// Build the command line for the session process, along with the
+    /// variables shell integration needs.
+    ///
+    /// Without a command this is the user's shell. A command runs directly
+    /// unless a login or interactive shell is requested, in which case it is
+    /// exec'd from that shell so rc files can set up PATH first.
+    fn build_command(options: &SpawnOptions) -> (CommandBuilder, Vec<(String, String)>) {
+        let shell = match &options.profile {
+            Some(profile) => profile.program.clone(),
+            None => std::env::var("SHELL").unwrap_or_else(|_| {
+                if cfg!(windows) {
+                    "cmd.exe".to_string()
+                } else {
+                    "/bin/bash".to_string()
+                }
+            }),
+        };
+
+        let is_shell = options.command.is_none();
+        let login = options.login.unwrap_or(is_shell);
+        let interactive = options.interactive.unwrap_or(is_shell);
+
+        let Some(program) = &options.command else {
+            #[cfg(unix)]
+            if options.shell_integration {
+                if let Some(launch) = super::shell_integration::command(&shell, login) {
+                    return launch;
+                }
+            }
+            let mut cmd = CommandBuilder::new(&shell);
+            if let Some(profile) = &options.profile {
+                cmd.args(&profile.args);
+                return (cmd, Vec::new());
+            }
+            // Start as interactive login shell to properly source all config files
+            // -l: login shell (sources .zprofile, .zlogin)
+            // -i: interactive shell (sources .zshrc where most config lives)
+            if login {
+                cmd.arg("-l");
+            }
+            if interactive {
+                cmd.arg("-i");
+            }
+            return (cmd, Vec::new());
+        };
+
+        if !login && !interactive {
+            let mut cmd = CommandBuilder::new(program);
+            cmd.args(&options.args);
+            return (cmd, Vec::new());
+        }
+
+        // Pass the program and its arguments positionally to avoid quoting
+        let mut cmd = CommandBuilder::new(&shell);
+        if login {
+            cmd.arg("-l");
+        }
+        if interactive {
+            cmd.arg("-i");
+        }
+        cmd.arg("-c");
+        cmd.arg("exec \"$0\" \"$@\"");
+        cmd.arg(program);
+        cmd.args(&options.args);
+        (cmd, Vec::new())
+    }
+
+    /// Reject options that cannot be honored together
+    fn validate(options: &SpawnOptions) -> Result<(), String> {
+        #[cfg(unix)]
+        if let Some(profile) = &options.profile {
+            if options.shell_integration
+                && !profile.args.is_empty()
+                && super::shell_integration::supports(&profile.program)
+            {
+                // Integration starts the shell with arguments of its own
+                return Err(format!(
+                    "Shell integration cannot be combined with the arguments of profile {}",
+                    profile.name
+                ));
+            }
+        }
+        let _ = options;
+        Ok(())
+    }
+
+    /// Whether the session's shell marks its prompts with OSC 133
+    fn emits_prompt_marks(options: &SpawnOptions) -> bool {
+        #[cfg(unix)]
+        if options.shell_integration && options.command.is_none() {
+            let shell = match &options.profile {
+                Some(profile) => profile.program.clone(),
+                None => std::env::var("SHELL").unwrap_or_default(),
+            };
+            return super::shell_integration::supports(&shell);
+        }
+        let _ = options;
+        false
+    }
+
+    /// Block until the shell shows its first prompt: its OSC 133 prompt
+    /// mark, or without marks, output followed by `PROMPT_QUIET` of
+    /// silence. Gives up after `PROMPT_TIMEOUT`.
+    fn wait_for_prompt(output: mpsc::Receiver<Vec<u8>>, marks: bool) {
+        let deadline = Instant::now() + PROMPT_TIMEOUT;
+        let mut osc = OscScanner::default();
+        let mut printed = false;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return;
+            }
+            let wait = if printed && !marks {
+                PROMPT_QUIET
+            } else {
+                deadline - now
+            };
+            match output.recv_timeout(wait) {
+                Ok(data) => {
+                    printed = true;
+                    let prompt = osc
+                        .feed(&data)
+                        .iter()
+                        .any(|(_, osc)| osc.code == 133 && osc.data.starts_with('A'));
+                    if prompt {
+                        return;
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) if printed && !marks => return,
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+
+    /// Command line starting `argv` in the session's sandbox, if it has
+    /// one, and under its resource limits, which are set up for it
+    fn launch_command(
+        session_id: &str,
+        options: &SpawnOptions,
+        argv: &[String],
+    ) -> Result<(SessionLimits, Vec<String>), String> {
+        let argv = Self::sandbox_command(options, argv)?.unwrap_or_else(|| argv.to_vec());
+        // Outermost, so the cgroup is joined outside the sandbox's namespaces
+        SessionLimits::prepare(session_id, options.resource_limits, argv)
+    }
+
+    /// Command line running `argv` in the session's sandbox, if it has one
+    fn sandbox_command(
+        options: &SpawnOptions,
+        argv: &[String],
+    ) -> Result<Option<Vec<String>>, String> {
+        let Some(sandbox) = &options.sandbox else {
+            return Ok(None);
+        };
+        if options.shell_integration {
+            // Its startup files live in the runtime directory, which the
+            // sandbox hides
+            return Err("Shell integration is not available in sandboxed sessions".to_string());
+        }
+        sandbox::command(argv.to_vec(), sandbox, options.cwd.as_deref()).map(Some)
+    }
+
+    /// Environment of the process: the app's variables the policy lets
+    /// through, the profile's, the ones given at spawn time, TERM, the ones
+    /// shell integration needs, and finally the secrets
+    fn environment(options: &SpawnOptions, integration: Vec<(String, String)>) -> Environment {
+        let profile = options.profile.as_ref();
+        let mut set: Vec<(String, String)> = profile
+            .map(|profile| profile.env.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .chain(options.env.clone().unwrap_or_default())
+            .collect();
+        // Set TERM for proper terminal emulation
+        let term = profile
+            .and_then(|profile| profile.term.clone())
+            .unwrap_or_else(|| "xterm-256color".to_string());
+        set.push(("TERM".to_string(), term));
+        set.extend(integration);
+        env::build(std::env::vars(), &options.env_policy, set, &options.secrets)
+    }
+
+    fn with_env_audit(mut self, audit: EnvAudit) -> Self {
+        if !audit.injected.is_empty() {
+            let variables: Vec<&str> = audit
+                .injected
+                .iter()
+                .map(|secret| secret.variable.as_str())
+                .collect();
+            eprintln!(
+                "Session {} injected secrets into {}",
+                self.session_id,
+                variables.join(", ")
+            );
+        }
+        self.env_audit = audit;
+        self
+    }
+
+    /// How the environment of the process was built; empty for sessions
+    /// restored from the session daemon
+    pub fn env_audit(&self) -> EnvAudit {
+        self.env_audit.clone()
+    }
+
+    /// Program and arguments of a command, for display
+    fn command_line(cmd: &CommandBuilder) -> Vec<String> {
+        cmd.get_argv()
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// Read output from PTY and queue it for delivery to the frontend
     fn read_output(
         mut reader: Box<dyn Read + Send>,
-        session_id: String,
         running: Arc<std::sync::atomic::AtomicBool>,
-        app: AppHandle,
+        chunks: mpsc::SyncSender<Vec<u8>>,
     ) {
         let mut buffer = [0u8; 4096];
 
@@ -141,83 +899,546 @@ impl PtySession {
 
             match reader.read(&mut buffer) {
                 Ok(0) => {
-                    // EOF - process exited
-                    let _ = app.emit(
-                        "terminal-exit",
-                        TerminalExitPayload {
-                            session_id: session_id.clone(),
-                            exit_code: Some(0),
-                        },
-                    );
+                    // EOF - process exited, the waiter reports the status
                     break;
                 }
                 Ok(n) => {
-                    let data = buffer[..n].to_vec();
-                    let _ = app.emit(
-                        "terminal-output",
-                        TerminalOutputPayload {
-                            session_id: session_id.clone(),
-                            data,
-                        },
-                    );
+                    if chunks.send(buffer[..n].to_vec()).is_err() {
+                        break;
+                    }
                 }
-                Err(e) => {
-                    eprintln!("PTY read error for {}: {}", session_id, e);
-                    let _ = app.emit(
-                        "terminal-exit",
-                        TerminalExitPayload {
-                            session_id: session_id.clone(),
-                            exit_code: None,
-                        },
-                    );
+                Err(_) => {
+                    // Linux reports EIO once the slave side is closed
                     break;
                 }
             }
         }
     }
 
+    /// Look up the foreground process, announcing it when it changed
+    fn sample_foreground(
+        session_id: &str,
+        host: &Host,
+        pid: Option<u32>,
+        foreground: &Mutex<Option<ForegroundProcess>>,
+        events: &EventEmitter,
+    ) {
+        let Some(process) = pid
+            .zip(host.foreground_pgrp(pid))
+            .and_then(|(leader, pgrp)| foreground::resolve(leader, pgrp))
+        else {
+            return;
+        };
+
+        let mut current = foreground.lock();
+        if current.as_ref() == Some(&process) {
+            return;
+        }
+        *current = Some(process.clone());
+        events.emit(
+            "terminal-foreground-changed",
+            TerminalForegroundPayload {
+                session_id: session_id.to_string(),
+                process,
+            },
+        );
+    }
+
+    /// Sample resource usage, emitting `terminal-stats` and a
+    /// `terminal-stats-warning` for each limit just crossed
+    fn sample_stats(session_id: &str, stats: &Mutex<StatsSampler>, events: &EventEmitter) {
+        let (sample, warnings) = {
+            let mut sampler = stats.lock();
+            let Some(sample) = sampler.sample() else {
+                return;
+            };
+            (sample, sampler.check_limits(&sample))
+        };
+
+        events.emit(
+            "terminal-stats",
+            TerminalStatsPayload {
+                session_id: session_id.to_string(),
+                stats: sample,
+            },
+        );
+        for warning in warnings {
+            events.emit(
+                "terminal-stats-warning",
+                TerminalStatsWarningPayload {
+                    session_id: session_id.to_string(),
+                    resource: warning.resource,
+                    value: warning.value,
+                    limit: warning.limit,
+                },
+            );
+        }
+    }
+
+    /// Emit the exit event for a reaped child
+    fn emit_exit(
+        events: &EventEmitter,
+        session_id: String,
+        exit: ChildExit,
+        limit: Option<ExceededLimit>,
+    ) {
+        events.emit(
+            "terminal-exit",
+            TerminalExitPayload {
+                session_id,
+                exit_code: exit.exit_code,
+                signal: exit.signal,
+                limit,
+            },
+        );
+    }
+
     /// Write input to the PTY
     pub fn write(&self, data: &[u8]) -> Result<(), String> {
-        let mut writer = self.writer.lock();
+        Self::write_input(&self.writer, &self.output, data)
+    }
+
+    fn write_input(
+        writer: &Mutex<Box<dyn Write + Send>>,
+        output: &Mutex<OutputStream>,
+        data: &[u8],
+    ) -> Result<(), String> {
+        let mut writer = writer.lock();
         writer
             .write_all(data)
             .map_err(|e| format!("Failed to write to PTY: {}", e))?;
         writer
             .flush()
             .map_err(|e| format!("Failed to flush PTY: {}", e))?;
+        output.lock().input(data);
         Ok(())
     }
 
+    /// Acknowledge that the frontend consumed output up to stream offset `seq`
+    pub fn ack(&self, seq: u64) {
+        self.flow.ack(seq);
+    }
+
     /// Resize the PTY
     pub fn resize(&self, cols: u16, rows: u16) -> Result<(), String> {
-        let master = self.master.lock();
-        master
-            .resize(PtySize {
-                rows,
-                cols,
-                pixel_width: 0,
-                pixel_height: 0,
-            })
-            .map_err(|e| format!("Failed to resize PTY: {}", e))?;
+        match &*self.host {
+            Host::Local { master, .. } => master
+                .lock()
+                .resize(PtySize {
+                    rows,
+                    cols,
+                    pixel_width: 0,
+                    pixel_height: 0,
+                })
+                .map_err(|e| format!("Failed to resize PTY: {}", e))?,
+            #[cfg(unix)]
+            Host::Daemon(connection) => connection
+                .resize(cols, rows)
+                .map_err(|e| format!("Failed to resize PTY: {}", e))?,
+        }
+        self.output.lock().resize(rows, cols);
         Ok(())
     }
 
-    /// Get the session ID (kept for potential debugging use)
-    #[allow(dead_code)]
-    pub fn id(&self) -> &str {
-        &self.session_id
+    /// Start recording the session to an asciicast file in `project_dir`
+    pub fn start_recording(&self, project_dir: &Path) -> Result<PathBuf, String> {
+        self.output.lock().start_recording(project_dir)
+    }
+
+    /// Stop recording, returning the path of the finished file
+    pub fn stop_recording(&self) -> Result<Option<PathBuf>, String> {
+        self.output.lock().stop_recording()
     }
 
-    /// Close the PTY session
-    pub fn close(&self) {
+    /// Capture the visible screen as tracked by the headless emulator
+    pub fn snapshot(&self, styled: bool) -> TerminalSnapshot {
+        self.output
+            .lock()
+            .screen()
+            .snapshot(&self.session_id, styled)
+    }
+
+    /// Snapshot the scrollback buffer for a reattaching frontend,
+    /// optionally moving output delivery to the frontend's new channel
+    pub fn attach(&self, channel: Option<OutputChannel>) -> TerminalAttachPayload {
+        let (data, seq) = self.output.lock().attach(channel);
+        // The new frontend starts from the snapshot, so nothing is in flight
+        self.flow.ack(seq);
+        TerminalAttachPayload {
+            session_id: self.session_id.clone(),
+            data,
+            seq,
+        }
+    }
+
+    /// Describe the session for the UI
+    pub fn info(&self) -> TerminalSessionInfo {
+        let exit = *self.exit.lock();
+        let foreground = self.foreground.lock().clone();
+        let timeouts = self.timeouts.lock().timeouts();
+        let stats_limits = self.stats.lock().limits();
+        let output = self.output.lock();
+        let (rows, cols) = output.screen().size();
+
+        TerminalSessionInfo {
+            session_id: self.session_id.clone(),
+            pid: self.pid,
+            command: self.command.clone(),
+            cwd: output.cwd().map(str::to_string),
+            created_at: info::unix_millis(self.created_at),
+            last_activity_at: info::unix_millis(output.last_input().max(output.last_output())),
+            last_input_at: info::unix_millis(output.last_input()),
+            last_output_at: info::unix_millis(output.last_output()),
+            rows,
+            cols,
+            title: output.screen().title().to_string(),
+            persistent: !matches!(*self.host, Host::Local { .. }),
+            running: exit.is_none(),
+            exit_code: exit.and_then(|e| e.exit_code),
+            signal: exit.and_then(|e| e.signal),
+            labels: self.labels.clone(),
+            foreground,
+            agent_state: output.agent_state(),
+            redactions: output.redactions(),
+            timeouts,
+            stats_limits,
+            resource_limits: self.limits.limits(),
+        }
+    }
+
+    /// Change the timeout policy, and the one the session daemon restores
+    /// a hosted session with
+    pub fn set_timeouts(&self, timeouts: SessionTimeouts) -> Result<(), String> {
+        #[cfg(unix)]
+        if let Host::Daemon(connection) = &*self.host {
+            connection
+                .set_timeouts(timeouts)
+                .map_err(|e| format!("Failed to update session daemon: {}", e))?;
+        }
+        self.timeouts.lock().set(timeouts);
+        Ok(())
+    }
+
+    /// Apply the timeout policy, emitting `terminal-idle` when the session
+    /// went quiet. Returns why the session should be closed, if it should,
+    /// after emitting `terminal-timeout`.
+    pub fn check_timeouts(&self) -> Option<TimeoutReason> {
+        let (last_input, last_output) = {
+            let output = self.output.lock();
+            (output.last_input(), output.last_output())
+        };
+        let check =
+            self.timeouts
+                .lock()
+                .check(SystemTime::now(), self.created_at, last_input, last_output);
+
+        if let Some(idle_for) = check.idle_for {
+            self.events.emit(
+                "terminal-idle",
+                TerminalIdlePayload {
+                    session_id: self.session_id.clone(),
+                    idle_ms: idle_for.as_millis() as u64,
+                    last_output_at: info::unix_millis(last_output),
+                },
+            );
+        }
+        if let Some(reason) = check.close {
+            self.events.emit(
+                "terminal-timeout",
+                TerminalTimeoutPayload {
+                    session_id: self.session_id.clone(),
+                    reason,
+                },
+            );
+        }
+        check.close
+    }
+
+    /// Resource usage of the session's process tree, as last sampled; sampled
+    /// now if it has not been yet
+    pub fn stats(&self) -> Option<ResourceStats> {
+        let mut sampler = self.stats.lock();
+        sampler.latest().or_else(|| sampler.sample())
+    }
+
+    /// Change the usage limits
+    pub fn set_stats_limits(&self, limits: StatsLimits) {
+        self.stats.lock().set_limits(limits);
+    }
+
+    /// Receive a copy of the session's output from now on
+    pub fn tap(&self) -> mpsc::Receiver<Vec<u8>> {
+        self.output.lock().tap()
+    }
+
+    /// How the process exited, once it has
+    pub fn exit_status(&self) -> Option<ChildExit> {
+        *self.exit.lock()
+    }
+
+    /// Emit `terminal-match` events when `pattern` matches the output
+    pub fn watch(&self, watcher_id: String, pattern: &str, once: bool) -> Result<(), String> {
+        self.output.lock().watch(watcher_id, pattern, once)
+    }
+
+    /// Remove an output watcher; returns false if it did not exist
+    pub fn unwatch(&self, watcher_id: &str) -> bool {
+        self.output.lock().unwatch(watcher_id)
+    }
+
+    /// Commands run in the session, at most the last `limit` of them
+    pub fn command_history(&self, limit: Option<usize>) -> Vec<CommandRecord> {
+        self.output.lock().command_history(limit)
+    }
+
+    /// Process currently in the foreground, as last sampled
+    pub fn foreground(&self) -> Option<ForegroundProcess> {
+        self.foreground.lock().clone()
+    }
+
+    /// Close the PTY session, terminating its whole process tree.
+    ///
+    /// Escalates from SIGHUP to SIGTERM to SIGKILL, giving the processes
+    /// `grace` to exit after each signal, then joins the reader and waiter
+    /// threads. Returns once teardown is confirmed, which can take seconds,
+    /// so async callers run it on a blocking thread.
+    pub fn close(&self, grace: Duration) -> Result<(), String> {
         self.running
             .store(false, std::sync::atomic::Ordering::Relaxed);
-        // The reader thread will exit when it detects running=false or gets EOF
+        // A paused delivery would keep the reader from ever seeing EOF
+        self.flow.release();
+        self.monitor_stop.lock().take();
+
+        let mut join_timeout = THREAD_JOIN_TIMEOUT;
+        match &*self.host {
+            #[cfg(unix)]
+            Host::Local { .. } => {
+                if let Some(pid) = self.pid {
+                    let foreground_pgrp = self.host.foreground_pgrp(self.pid);
+                    if !child::terminate_tree(pid, foreground_pgrp, grace) {
+                        return Err(format!(
+                            "Processes of session {} did not exit",
+                            self.session_id
+                        ));
+                    }
+                }
+            }
+            #[cfg(not(unix))]
+            Host::Local { killer, .. } => {
+                let _ = killer.lock().kill();
+            }
+            #[cfg(unix)]
+            Host::Daemon(connection) => {
+                // The daemon escalates through the same signals, and the
+                // output ends once it has reaped the process
+                connection
+                    .close(grace)
+                    .map_err(|e| format!("Failed to close session: {}", e))?;
+                join_timeout += grace * 3;
+            }
+        }
+
+        // With every process gone the reader sees EOF and the waiter reaps
+        let deadline = Instant::now() + join_timeout;
+        for handle in [
+            &self.waiter_handle,
+            &self.reader_handle,
+            &self.monitor_handle,
+        ] {
+            let mut handle = handle.lock();
+            while handle.as_ref().is_some_and(|h| !h.is_finished()) && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(10));
+            }
+            match handle.take() {
+                Some(h) if h.is_finished() => {
+                    let _ = h.join();
+                }
+                Some(h) => {
+                    *handle = Some(h);
+                    return Err(format!("Session {} threads did not stop", self.session_id));
+                }
+                None => {}
+            }
+        }
+
+        self.limits.release();
+        Ok(())
     }
 }
 
 impl Drop for PtySession {
     fn drop(&mut self) {
-        self.close();
+        // Hosted sessions outlive the app, so only disconnect from them
+        #[cfg(unix)]
+        if let Host::Daemon(connection) = &*self.host {
+            connection.detach();
+            return;
+        }
+        // Already torn down by an explicit close
+        if !self.running.load(std::sync::atomic::Ordering::Relaxed) {
+            return;
+        }
+        let _ = self.close(DEFAULT_CLOSE_GRACE);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn argv(options: &SpawnOptions) -> Vec<String> {
+        PtySession::build_command(options)
+            .0
+            .get_argv()
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_build_command_defaults_to_login_shell() {
+        let argv = argv(&SpawnOptions::default());
+        assert_eq!(&argv[1..], ["-l", "-i"]);
+    }
+
+    #[test]
+    fn test_build_command_uses_profile() {
+        let profile = ShellProfile {
+            name: "fish".to_string(),
+            program: "/usr/bin/fish".to_string(),
+            args: vec!["--private".to_string()],
+            env: HashMap::from([("EDITOR".to_string(), "vi".to_string())]),
+            initial_command: None,
+            term: Some("xterm-kitty".to_string()),
+        };
+        let options = SpawnOptions {
+            env: Some(HashMap::from([("EDITOR".to_string(), "nano".to_string())])),
+            profile: Some(profile),
+            ..Default::default()
+        };
+        assert_eq!(argv(&options), ["/usr/bin/fish", "--private"]);
+
+        let env = PtySession::environment(&options, Vec::new()).vars;
+        let value = |key: &str| env.iter().rev().find(|(k, _)| k == key).unwrap().1.clone();
+        assert_eq!(value("EDITOR"), "nano");
+        assert_eq!(value("TERM"), "xterm-kitty");
+    }
+
+    fn sh_profile(args: &[&str], initial_command: &str) -> ShellProfile {
+        ShellProfile {
+            name: "sh".to_string(),
+            program: "/bin/sh".to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            env: HashMap::new(),
+            initial_command: Some(initial_command.to_string()),
+            term: None,
+        }
+    }
+
+    #[test]
+    fn test_initial_command_waits_for_prompt() {
+        // The prompt only shows up after a delay the command would be
+        // typed into otherwise
+        let profile = sh_profile(
+            &[
+                "-c",
+                "sleep 1; printf 'ready> '; read line; echo \"got $line\"; sleep 5",
+            ],
+            "hello",
+        );
+        let session = PtySession::spawn(
+            "initial-command".to_string(),
+            SpawnOptions {
+                profile: Some(profile),
+                ..Default::default()
+            },
+            EventEmitter::discard(),
+        )
+        .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !session
+            .snapshot(false)
+            .lines
+            .iter()
+            .any(|line| line == "got hello")
+        {
+            assert!(Instant::now() < deadline, "initial command never ran");
+            thread::sleep(Duration::from_millis(50));
+        }
+        // Typed after the prompt, so echoed once, right after it
+        let lines = session.snapshot(false).lines;
+        assert_eq!(lines[0], "ready> hello");
+        session.close(Duration::from_millis(100)).unwrap();
+    }
+
+    #[test]
+    fn test_shell_integration_rejects_profile_arguments() {
+        let mut profile = sh_profile(&["--norc"], "true");
+        profile.program = "/bin/bash".to_string();
+        let options = SpawnOptions {
+            profile: Some(profile),
+            shell_integration: true,
+            ..Default::default()
+        };
+        assert!(PtySession::validate(&options).is_err());
+    }
+
+    #[test]
+    fn test_shell_integration_survives_env_policy() {
+        let options = SpawnOptions {
+            profile: Some(ShellProfile {
+                name: "zsh".to_string(),
+                program: "/bin/zsh".to_string(),
+                args: Vec::new(),
+                env: HashMap::new(),
+                initial_command: None,
+                term: None,
+            }),
+            shell_integration: true,
+            env_policy: EnvPolicy {
+                allow: Some(vec!["PATH".to_string()]),
+                deny: Vec::new(),
+            },
+            ..Default::default()
+        };
+        let (cmd, integration) = PtySession::build_command(&options);
+        assert!(cmd.get_argv().iter().any(|arg| arg == "-i"));
+
+        let env = PtySession::environment(&options, integration);
+        assert!(env.clear);
+        let zdotdir = env.vars.iter().find(|(key, _)| key == "ZDOTDIR").unwrap();
+        assert!(Path::new(&zdotdir.1).join(".zshrc").is_file());
+    }
+
+    #[test]
+    fn test_build_command_runs_program_directly() {
+        let options = SpawnOptions {
+            command: Some("npm".to_string()),
+            args: vec!["test".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(argv(&options), ["npm", "test"]);
+    }
+
+    #[test]
+    fn test_build_command_execs_program_from_login_shell() {
+        let options = SpawnOptions {
+            command: Some("claude".to_string()),
+            args: vec!["--resume".to_string(), "a b".to_string()],
+            login: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            &argv(&options)[1..],
+            [
+                "-l",
+                "-c",
+                "exec \"$0\" \"$@\"",
+                "claude",
+                "--resume",
+                "a b"
+            ]
+        );
     }
 }
@@ -0,0 +1,68 @@
+# SpecFlux shell integration for bash, loaded with --init-file.
+#
+# Marks prompts and commands with OSC 133 and reports the working directory
+# with OSC 7, then gets out of the way.
+
+if [[ -n $SPECFLUX_SHELL_LOGIN ]]; then
+    unset SPECFLUX_SHELL_LOGIN
+    [[ -r /etc/profile ]] && . /etc/profile
+    for __specflux_rc in ~/.bash_profile ~/.bash_login ~/.profile; do
+        if [[ -r $__specflux_rc ]]; then
+            . "$__specflux_rc"
+            break
+        fi
+    done
+    unset __specflux_rc
+else
+    [[ -r /etc/bash.bashrc ]] && . /etc/bash.bashrc
+    [[ -r ~/.bashrc ]] && . ~/.bashrc
+fi
+
+if [[ -z $__specflux_integrated ]]; then
+    __specflux_integrated=1
+
+    __specflux_urlencode() {
+        local LC_ALL=C text=$1 encoded= char i
+        for ((i = 0; i < ${#text}; i++)); do
+            char=${text:i:1}
+            case $char in
+                [a-zA-Z0-9/._~-]) encoded+=$char ;;
+                *) printf -v char '%%%02X' "'$char"; encoded+=$char ;;
+            esac
+        done
+        printf '%s' "$encoded"
+    }
+
+    # Runs first in PROMPT_COMMAND so $? is still the command's status
+    __specflux_precmd() {
+        local status=$?
+        __specflux_in_prompt=1
+        if [[ -n $__specflux_running ]]; then
+            printf '\e]133;D;%s\a' "$status"
+            __specflux_running=
+        fi
+        printf '\e]7;file://%s%s\a' "$HOSTNAME" "$(__specflux_urlencode "$PWD")"
+        return $status
+    }
+
+    # Runs last, after prompt themes had their chance to rewrite PS1
+    __specflux_ps1() {
+        if [[ $PS1 != *'133;A'* ]]; then
+            PS1='\[\e]133;A\a\]'$PS1'\[\e]133;B\a\]'
+        fi
+        __specflux_in_prompt=
+    }
+
+    __specflux_preexec() {
+        [[ -n $__specflux_in_prompt || -n $__specflux_running || -n $COMP_LINE ]] && return
+        [[ $BASH_COMMAND == __specflux_precmd ]] && return
+        __specflux_running=1
+        local command
+        command=$(HISTTIMEFORMAT= builtin history 1)
+        command=${command#*[0-9]  }
+        printf '\e]133;C;cmdline_url=%s\a' "$(__specflux_urlencode "${command:-$BASH_COMMAND}")"
+    }
+
+    PROMPT_COMMAND="__specflux_precmd${PROMPT_COMMAND:+; $PROMPT_COMMAND}; __specflux_ps1"
+    trap '__specflux_preexec' DEBUG
+fi
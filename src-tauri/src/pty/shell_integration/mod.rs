@@ -0,0 +1,148 @@
+//! Shell Integration
+//!
+//! Starts bash and zsh with generated startup files that load the user's
+//! own configuration and then add OSC 133 prompt and command marks, from
+//! which `history` records the commands a session runs.
+
+use portable_pty::CommandBuilder;
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+
+const BASH_INIT: &str = include_str!("bash.sh");
+const ZSH_FILES: [(&str, &str); 3] = [
+    (".zshenv", include_str!("zsh/.zshenv")),
+    (".zprofile", include_str!("zsh/.zprofile")),
+    (".zshrc", include_str!("zsh/.zshrc")),
+];
+
+/// Whether `shell` is one integration is available for
+pub fn supports(shell: &str) -> bool {
+    Path::new(shell)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| matches!(name, "bash" | "zsh"))
+}
+
+/// Variables the integrated shell needs, to be set after the environment
+/// policy has been applied
+pub type IntegrationEnv = Vec<(String, String)>;
+
+/// Command starting `shell` with integration and the variables it needs,
+/// or None for other shells
+pub fn command(shell: &str, login: bool) -> Option<(CommandBuilder, IntegrationEnv)> {
+    let name = Path::new(shell).file_name()?.to_str()?;
+    let result = match name {
+        "bash" => bash_command(shell, login),
+        "zsh" => zsh_command(shell, login),
+        _ => return None,
+    };
+    result
+        .map_err(|e| eprintln!("Shell integration unavailable: {}", e))
+        .ok()
+}
+
+/// bash ignores rc files given to login shells, so the init file loads
+/// the profile itself when asked to
+fn bash_command(shell: &str, login: bool) -> io::Result<(CommandBuilder, IntegrationEnv)> {
+    let init_file = install_dir()?.join("bash.sh");
+    write_if_changed(&init_file, BASH_INIT)?;
+
+    let mut cmd = CommandBuilder::new(shell);
+    cmd.arg("--init-file");
+    cmd.arg(&init_file);
+    cmd.arg("-i");
+    let mut env = Vec::new();
+    if login {
+        env.push(("SPECFLUX_SHELL_LOGIN".to_string(), "1".to_string()));
+    }
+    Ok((cmd, env))
+}
+
+fn zsh_command(shell: &str, login: bool) -> io::Result<(CommandBuilder, IntegrationEnv)> {
+    let dir = install_dir()?.join("zsh");
+    DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+    for (name, contents) in ZSH_FILES {
+        write_if_changed(&dir.join(name), contents)?;
+    }
+
+    let mut cmd = CommandBuilder::new(shell);
+    if login {
+        cmd.arg("-l");
+    }
+    cmd.arg("-i");
+    let mut env = Vec::new();
+    if let Ok(user_dir) = std::env::var("ZDOTDIR") {
+        env.push(("SPECFLUX_USER_ZDOTDIR".to_string(), user_dir));
+    }
+    env.push(("ZDOTDIR".to_string(), dir.to_string_lossy().into_owned()));
+    Ok((cmd, env))
+}
+
+/// Directory the startup files are written to, private to the user
+fn install_dir() -> io::Result<PathBuf> {
+    let runtime_dir = super::daemon::runtime_dir();
+    super::daemon::create_private_dir(&runtime_dir)?;
+    let dir = runtime_dir.join("shell-integration");
+    DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+    Ok(dir)
+}
+
+/// Write a startup file, replacing it atomically so a shell starting
+/// concurrently never reads half of it
+fn write_if_changed(path: &Path, contents: &str) -> io::Result<()> {
+    if fs::read_to_string(path).is_ok_and(|current| current == contents) {
+        return Ok(());
+    }
+    let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&partial, contents)?;
+    fs::rename(&partial, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pty::history::CommandHistory;
+    use crate::pty::osc::OscScanner;
+    use portable_pty::{native_pty_system, PtySize};
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_bash_marks_commands() {
+        if !Path::new("/bin/bash").exists() {
+            return;
+        }
+        let pair = native_pty_system().openpty(PtySize::default()).unwrap();
+        let (mut cmd, env) = command("/bin/bash", false).unwrap();
+        for (key, value) in env {
+            cmd.env(key, value);
+        }
+        // Keep the user's rc files out of the test
+        cmd.env("HOME", "/nonexistent");
+        cmd.cwd(std::env::temp_dir());
+        let mut child = pair.slave.spawn_command(cmd).unwrap();
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader().unwrap();
+        let mut writer = pair.master.take_writer().unwrap();
+        writer.write_all(b"echo integrated; false\nexit\n").unwrap();
+
+        let mut output = Vec::new();
+        let mut buffer = [0u8; 4096];
+        while let Ok(n @ 1..) = reader.read(&mut buffer) {
+            output.extend_from_slice(&buffer[..n]);
+        }
+        child.wait().unwrap();
+
+        let mut history = CommandHistory::default();
+        let sequences = OscScanner::default().feed(&output);
+        history.feed(0, &output, &sequences, None);
+        let records = history.records(None);
+
+        assert_eq!(records[0].command, "echo integrated; false");
+        assert_eq!(records[0].exit_code, Some(1));
+        assert_eq!(records[0].output.trim(), "integrated");
+        assert_eq!(records[1].command, "exit");
+    }
+}
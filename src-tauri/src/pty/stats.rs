@@ -0,0 +1,281 @@
+//! Session Resource Usage
+//!
+//! Samples the CPU, memory, threads and open files of the process tree
+//! under a session's child, so a runaway build or agent stands out, and
+//! warns when a session crosses a configured limit. Sampling needs `/proc`
+//! and so only works on Linux.
+
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+use std::collections::HashSet;
+#[cfg(target_os = "linux")]
+use std::time::{Instant, SystemTime};
+
+#[cfg(target_os = "linux")]
+use super::info;
+#[cfg(target_os = "linux")]
+use super::procfs::{self, ProcStat};
+
+/// Resources used by a session's process tree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceStats {
+    /// Unix time in milliseconds
+    pub sampled_at: u64,
+    pub processes: u64,
+    /// CPU time used since the previous sample, 100 being one full core
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    pub threads: u64,
+    pub open_fds: u64,
+}
+
+/// Usage above which a session is warned about; every limit is off unless
+/// set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StatsLimits {
+    pub cpu_percent: Option<f64>,
+    pub rss_bytes: Option<u64>,
+    pub threads: Option<u64>,
+    pub open_fds: Option<u64>,
+}
+
+/// A resource with a limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StatsResource {
+    CpuPercent,
+    RssBytes,
+    Threads,
+    OpenFds,
+}
+
+/// A limit a session just crossed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsWarning {
+    pub resource: StatsResource,
+    pub value: f64,
+    pub limit: f64,
+}
+
+/// Samples one session's process tree
+pub struct StatsSampler {
+    root: Option<u32>,
+    limits: StatsLimits,
+    latest: Option<ResourceStats>,
+    /// Limits exceeded at the latest sample, so each crossing warns once
+    exceeded: HashSet<StatsResource>,
+    /// CPU ticks by pid and when they were read, to turn the next sample's
+    /// ticks into a percentage
+    #[cfg(target_os = "linux")]
+    previous: Option<(Instant, HashMap<i32, u64>)>,
+}
+
+impl StatsSampler {
+    /// Sampler for the tree under `root`, the session's child
+    pub fn new(root: Option<u32>, limits: StatsLimits) -> Self {
+        Self {
+            root,
+            limits,
+            latest: None,
+            exceeded: HashSet::new(),
+            #[cfg(target_os = "linux")]
+            previous: None,
+        }
+    }
+
+    pub fn limits(&self) -> StatsLimits {
+        self.limits
+    }
+
+    /// Replace the limits; a limit still exceeded warns again at the next
+    /// sample
+    pub fn set_limits(&mut self, limits: StatsLimits) {
+        self.limits = limits;
+        self.exceeded.clear();
+    }
+
+    /// The most recent sample
+    pub fn latest(&self) -> Option<ResourceStats> {
+        self.latest
+    }
+
+    /// Sample the process tree now; None once the child is gone or where
+    /// there is no `/proc`
+    #[cfg(target_os = "linux")]
+    pub fn sample(&mut self) -> Option<ResourceStats> {
+        let tree = procfs::process_tree(self.root? as i32);
+        if tree.is_empty() {
+            return None;
+        }
+        let open_fds = tree
+            .iter()
+            .filter_map(|stat| procfs::open_fds(stat.pid))
+            .sum();
+        let stats = self.record(&tree, open_fds, Instant::now(), SystemTime::now());
+        self.latest = Some(stats);
+        Some(stats)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn sample(&mut self) -> Option<ResourceStats> {
+        let _ = self.root;
+        None
+    }
+
+    /// Turn the counters of a process tree into stats
+    #[cfg(target_os = "linux")]
+    fn record(
+        &mut self,
+        tree: &[ProcStat],
+        open_fds: u64,
+        now: Instant,
+        sampled_at: SystemTime,
+    ) -> ResourceStats {
+        let ticks: HashMap<i32, u64> = tree.iter().map(|stat| (stat.pid, stat.cpu_ticks)).collect();
+
+        // Processes started since the previous sample count all their ticks
+        let cpu_percent = match &self.previous {
+            Some((then, previous)) if now > *then => {
+                let used: u64 = ticks
+                    .iter()
+                    .map(|(pid, &ticks)| {
+                        ticks.saturating_sub(previous.get(pid).copied().unwrap_or(0))
+                    })
+                    .sum();
+                let seconds = now.duration_since(*then).as_secs_f64();
+                used as f64 / clock_ticks_per_second() as f64 / seconds * 100.0
+            }
+            _ => 0.0,
+        };
+        self.previous = Some((now, ticks));
+
+        ResourceStats {
+            sampled_at: info::unix_millis(sampled_at),
+            processes: tree.len() as u64,
+            cpu_percent,
+            rss_bytes: tree.iter().map(|stat| stat.rss_pages).sum::<u64>() * page_size(),
+            threads: tree.iter().map(|stat| stat.threads).sum(),
+            open_fds,
+        }
+    }
+
+    /// Limits `stats` exceeds that the previous sample did not
+    pub fn check_limits(&mut self, stats: &ResourceStats) -> Vec<StatsWarning> {
+        let checks = [
+            (
+                StatsResource::CpuPercent,
+                stats.cpu_percent,
+                self.limits.cpu_percent,
+            ),
+            (
+                StatsResource::RssBytes,
+                stats.rss_bytes as f64,
+                self.limits.rss_bytes.map(|limit| limit as f64),
+            ),
+            (
+                StatsResource::Threads,
+                stats.threads as f64,
+                self.limits.threads.map(|limit| limit as f64),
+            ),
+            (
+                StatsResource::OpenFds,
+                stats.open_fds as f64,
+                self.limits.open_fds.map(|limit| limit as f64),
+            ),
+        ];
+
+        let mut warnings = Vec::new();
+        for (resource, value, limit) in checks {
+            match limit {
+                Some(limit) if value > limit => {
+                    if self.exceeded.insert(resource) {
+                        warnings.push(StatsWarning {
+                            resource,
+                            value,
+                            limit,
+                        });
+                    }
+                }
+                _ => {
+                    self.exceeded.remove(&resource);
+                }
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn clock_ticks_per_second() -> u64 {
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as u64,
+        _ => 100,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn page_size() -> u64 {
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as u64,
+        _ => 4096,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_warn_once_per_crossing() {
+        let mut sampler = StatsSampler::new(
+            None,
+            StatsLimits {
+                rss_bytes: Some(1000),
+                open_fds: Some(10),
+                ..Default::default()
+            },
+        );
+        let stats = |rss_bytes, open_fds| ResourceStats {
+            rss_bytes,
+            open_fds,
+            ..Default::default()
+        };
+
+        let warnings = sampler.check_limits(&stats(2000, 5));
+        assert_eq!(
+            warnings,
+            [StatsWarning {
+                resource: StatsResource::RssBytes,
+                value: 2000.0,
+                limit: 1000.0,
+            }]
+        );
+        assert!(sampler.check_limits(&stats(3000, 5)).is_empty());
+        assert!(sampler.check_limits(&stats(500, 5)).is_empty());
+        let warnings = sampler.check_limits(&stats(1500, 20));
+        assert_eq!(warnings.len(), 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sample_measures_own_process() {
+        let mut sampler = StatsSampler::new(Some(std::process::id()), StatsLimits::default());
+        let first = sampler.sample().unwrap();
+        assert!(first.processes >= 1);
+        assert!(first.threads >= 1);
+        assert!(first.rss_bytes > 0);
+        assert!(first.open_fds > 0);
+        assert_eq!(first.cpu_percent, 0.0);
+
+        let start = Instant::now();
+        while start.elapsed() < std::time::Duration::from_millis(200) {
+            std::hint::black_box(start.elapsed());
+        }
+        let second = sampler.sample().unwrap();
+        assert!(second.cpu_percent > 0.0);
+        assert_eq!(sampler.latest(), Some(second));
+    }
+}
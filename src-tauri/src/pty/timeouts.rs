@@ -0,0 +1,155 @@
+//! Session Timeouts
+//!
+//! Per-session policies that announce when a session has gone quiet and
+//! close sessions left idle, or running, for too long, so forgotten agent
+//! terminals do not pile up.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// Timeout policy of a session; every timeout is off unless set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SessionTimeouts {
+    /// Emit `terminal-idle` once the session printed nothing for this long
+    pub idle_event_ms: Option<u64>,
+    /// Close the session after this long without input or output
+    pub close_after_idle_ms: Option<u64>,
+    /// Close the session this long after it started, busy or not
+    pub max_lifetime_ms: Option<u64>,
+}
+
+/// Why a session was closed by a timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TimeoutReason {
+    Idle,
+    Lifetime,
+}
+
+/// What a timeout check found
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TimeoutCheck {
+    /// The session just went quiet for the idle event threshold; holds how
+    /// long it has been quiet
+    pub idle_for: Option<Duration>,
+    /// The session should be closed
+    pub close: Option<TimeoutReason>,
+}
+
+/// Applies a session's timeout policy
+#[derive(Default)]
+pub struct TimeoutTracker {
+    timeouts: SessionTimeouts,
+    /// Last output time the idle event was emitted for, so it is emitted
+    /// once per quiet period
+    idle_reported: Option<SystemTime>,
+}
+
+impl TimeoutTracker {
+    pub fn new(timeouts: SessionTimeouts) -> Self {
+        Self {
+            timeouts,
+            idle_reported: None,
+        }
+    }
+
+    pub fn timeouts(&self) -> SessionTimeouts {
+        self.timeouts
+    }
+
+    /// Replace the policy; an idle event already emitted for the current
+    /// quiet period is not repeated
+    pub fn set(&mut self, timeouts: SessionTimeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Evaluate the policy at `now`
+    pub fn check(
+        &mut self,
+        now: SystemTime,
+        created_at: SystemTime,
+        last_input: SystemTime,
+        last_output: SystemTime,
+    ) -> TimeoutCheck {
+        let since = |time: SystemTime| now.duration_since(time).unwrap_or_default();
+        let exceeds = |elapsed: Duration, limit: Option<u64>| {
+            limit.is_some_and(|ms| elapsed >= Duration::from_millis(ms))
+        };
+
+        let quiet = since(last_output);
+        let idle_for = (exceeds(quiet, self.timeouts.idle_event_ms)
+            && self.idle_reported != Some(last_output))
+        .then(|| {
+            self.idle_reported = Some(last_output);
+            quiet
+        });
+
+        let close = if exceeds(since(created_at), self.timeouts.max_lifetime_ms) {
+            Some(TimeoutReason::Lifetime)
+        } else if exceeds(
+            since(last_input.max(last_output)),
+            self.timeouts.close_after_idle_ms,
+        ) {
+            Some(TimeoutReason::Idle)
+        } else {
+            None
+        };
+
+        TimeoutCheck { idle_for, close }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_idle_event_once_per_quiet_period() {
+        let mut tracker = TimeoutTracker::new(SessionTimeouts {
+            idle_event_ms: Some(60_000),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            tracker.check(at(30), at(0), at(0), at(0)),
+            TimeoutCheck::default()
+        );
+        let check = tracker.check(at(61), at(0), at(0), at(0));
+        assert_eq!(check.idle_for, Some(Duration::from_secs(61)));
+        assert_eq!(tracker.check(at(120), at(0), at(0), at(0)).idle_for, None);
+
+        // Input alone does not end the quiet period, output does
+        assert_eq!(tracker.check(at(200), at(0), at(190), at(0)).idle_for, None);
+        assert!(tracker
+            .check(at(300), at(0), at(0), at(200))
+            .idle_for
+            .is_some());
+    }
+
+    #[test]
+    fn test_close_after_idle_or_lifetime() {
+        let mut tracker = TimeoutTracker::new(SessionTimeouts {
+            close_after_idle_ms: Some(600_000),
+            ..Default::default()
+        });
+        assert_eq!(tracker.check(at(700), at(0), at(500), at(0)).close, None);
+        assert_eq!(
+            tracker.check(at(1100), at(0), at(500), at(0)).close,
+            Some(TimeoutReason::Idle)
+        );
+
+        tracker.set(SessionTimeouts {
+            max_lifetime_ms: Some(3_600_000),
+            ..tracker.timeouts()
+        });
+        assert_eq!(
+            tracker.check(at(3600), at(0), at(3599), at(3599)).close,
+            Some(TimeoutReason::Lifetime)
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! UTF-8 Chunk Boundaries
+//!
+//! PTY reads split output at arbitrary byte offsets, which can cut a
+//! multi-byte character in two. These helpers find such a cut so the
+//! incomplete tail can be held back until the rest arrives.
+
+/// Length of an incomplete UTF-8 sequence at the end of `bytes`.
+///
+/// Returns 0 when `bytes` ends on a character boundary or when the tail is
+/// not the start of a valid sequence (binary output is passed through).
+pub fn incomplete_tail_len(bytes: &[u8]) -> usize {
+    // A sequence is at most 4 bytes, so only the last 3 can be incomplete
+    for back in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - back];
+        if byte & 0b1100_0000 == 0b1000_0000 {
+            // Continuation byte, keep looking for the lead byte
+            continue;
+        }
+        let expected = match byte {
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => return 0,
+        };
+        return if back < expected { back } else { 0 };
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_text_has_no_tail() {
+        assert_eq!(incomplete_tail_len(b"plain ascii"), 0);
+        assert_eq!(incomplete_tail_len("caf\u{e9}".as_bytes()), 0);
+        assert_eq!(incomplete_tail_len("\u{1F980}".as_bytes()), 0);
+        assert_eq!(incomplete_tail_len(b""), 0);
+    }
+
+    #[test]
+    fn test_split_characters_are_detected() {
+        let crab = "\u{1F980}".as_bytes();
+        for cut in 1..crab.len() {
+            let mut chunk = b"ok ".to_vec();
+            chunk.extend_from_slice(&crab[..cut]);
+            assert_eq!(incomplete_tail_len(&chunk), cut);
+        }
+
+        let euro = "\u{20AC}".as_bytes();
+        assert_eq!(incomplete_tail_len(&euro[..2]), 2);
+    }
+
+    #[test]
+    fn test_invalid_bytes_are_not_held_back() {
+        assert_eq!(incomplete_tail_len(&[b'a', 0xFF]), 0);
+        assert_eq!(incomplete_tail_len(&[0x80, 0x80, 0x80]), 0);
+    }
+}
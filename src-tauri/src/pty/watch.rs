@@ -0,0 +1,239 @@
+//! Output Pattern Watchers
+//!
+//! Matches regexes registered by the frontend against the plain text of a
+//! session's output, e.g. a confirmation prompt, a test summary or a pull
+//! request URL. Output is kept in a bounded window so matches may span
+//! chunks.
+
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::ansi::AnsiStripper;
+use super::utf8;
+
+/// Plain text kept for matches spanning chunks
+const MAX_WINDOW_BYTES: usize = 16 * 1024;
+
+/// A watcher's pattern matched the output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchMatch {
+    pub watcher_id: String,
+    /// The whole match
+    pub text: String,
+    /// Numbered capture groups, `None` where a group did not participate
+    pub groups: Vec<Option<String>>,
+    /// Named capture groups that participated
+    pub named: HashMap<String, String>,
+}
+
+impl WatchMatch {
+    fn new(watcher: &Watcher, captures: &Captures) -> Self {
+        let text = |m: regex::Match| m.as_str().to_string();
+        Self {
+            watcher_id: watcher.id.clone(),
+            text: captures.get(0).map(text).unwrap_or_default(),
+            groups: captures.iter().skip(1).map(|m| m.map(text)).collect(),
+            named: watcher
+                .regex
+                .capture_names()
+                .flatten()
+                .filter_map(|name| Some((name.to_string(), text(captures.name(name)?))))
+                .collect(),
+        }
+    }
+}
+
+struct Watcher {
+    id: String,
+    regex: Regex,
+    /// Remove after the first match
+    once: bool,
+    /// Window offset where the next match may start
+    scan_from: usize,
+}
+
+/// The watchers of one session and the text they are matched against
+pub struct Watchers {
+    watchers: Vec<Watcher>,
+    stripper: AnsiStripper,
+    /// Stripped text not yet in the window, ending in a split character
+    partial: Vec<u8>,
+    window: String,
+    /// Output arrived since the last `settle`
+    fresh: bool,
+    /// Matches at the end of the window were reported since output stopped
+    settled: bool,
+}
+
+impl Default for Watchers {
+    fn default() -> Self {
+        Self {
+            watchers: Vec::new(),
+            stripper: AnsiStripper::default(),
+            partial: Vec::new(),
+            window: String::new(),
+            fresh: false,
+            settled: true,
+        }
+    }
+}
+
+impl Watchers {
+    /// Watch output from now on for `pattern`, replacing a watcher with
+    /// the same ID
+    pub fn add(&mut self, id: String, pattern: &str, once: bool) -> Result<(), String> {
+        let regex = Regex::new(pattern).map_err(|e| format!("Invalid pattern: {}", e))?;
+        if regex.is_match("") {
+            return Err(format!("Pattern {:?} matches empty text", pattern));
+        }
+        self.remove(&id);
+        self.watchers.push(Watcher {
+            id,
+            regex,
+            once,
+            scan_from: self.window.len(),
+        });
+        Ok(())
+    }
+
+    /// Stop a watcher; returns false if there was none with this ID
+    pub fn remove(&mut self, id: &str) -> bool {
+        let before = self.watchers.len();
+        self.watchers.retain(|watcher| watcher.id != id);
+        self.watchers.len() != before
+    }
+
+    /// Add a chunk of raw output, returning the matches completed by it.
+    ///
+    /// A match reaching the end of the output so far might still grow
+    /// (think of a URL cut in half), so it is held back until more output
+    /// arrives or the session goes quiet.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<WatchMatch> {
+        self.stripper.feed(data, &mut self.partial);
+        if self.watchers.is_empty() {
+            self.partial.clear();
+            self.window.clear();
+            return Vec::new();
+        }
+
+        let complete = self.partial.len() - utf8::incomplete_tail_len(&self.partial);
+        let tail = self.partial.split_off(complete);
+        self.window
+            .push_str(&String::from_utf8_lossy(&self.partial));
+        self.partial = tail;
+        self.fresh = true;
+        self.settled = false;
+
+        let matches = self.scan(false);
+        self.trim();
+        matches
+    }
+
+    /// Called periodically; once no output arrived for a whole period,
+    /// reports the matches held back at the end of the output
+    pub fn settle(&mut self) -> Vec<WatchMatch> {
+        if std::mem::take(&mut self.fresh) || self.settled {
+            return Vec::new();
+        }
+        self.settled = true;
+        self.scan(true)
+    }
+
+    fn scan(&mut self, settled: bool) -> Vec<WatchMatch> {
+        let window = &self.window;
+        let mut found = Vec::new();
+        self.watchers.retain_mut(|watcher| {
+            while let Some(captures) = watcher.regex.captures_at(window, watcher.scan_from) {
+                let end = captures.get(0).map_or(window.len(), |m| m.end());
+                if end == window.len() && !settled {
+                    break;
+                }
+                watcher.scan_from = end;
+                found.push(WatchMatch::new(watcher, &captures));
+                if watcher.once {
+                    return false;
+                }
+            }
+            true
+        });
+        found
+    }
+
+    /// Drop text no watcher needs anymore, keeping the start of the line
+    /// the earliest watcher is in so `^` and `\b` see the right context
+    fn trim(&mut self) {
+        let excess = self.window.len().saturating_sub(MAX_WINDOW_BYTES);
+        let needed = self
+            .watchers
+            .iter()
+            .map(|watcher| watcher.scan_from)
+            .min()
+            .unwrap_or(self.window.len());
+        let line_start = self.window[..needed].rfind('\n').map_or(0, |i| i + 1);
+        let mut cut = line_start.max(excess);
+        while !self.window.is_char_boundary(cut) {
+            cut += 1;
+        }
+
+        self.window.drain(..cut);
+        for watcher in &mut self.watchers {
+            watcher.scan_from = watcher.scan_from.saturating_sub(cut);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_span_chunks_and_escape_sequences() {
+        let mut watchers = Watchers::default();
+        watchers
+            .add("tests".into(), r"Tests: (?P<failed>\d+) failed", false)
+            .unwrap();
+
+        assert!(watchers.feed(b"\x1b[1mTests:\x1b[0m \x1b[31m1").is_empty());
+        let found = watchers.feed(b"2 failed\x1b[0m, 40 passed\r\n");
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].text, "Tests: 12 failed");
+        assert_eq!(found[0].groups, [Some("12".to_string())]);
+        assert_eq!(found[0].named["failed"], "12");
+    }
+
+    #[test]
+    fn test_match_at_end_waits_for_more_output_or_quiet() {
+        let mut watchers = Watchers::default();
+        watchers
+            .add("pr".into(), r"https://github\.com/\S+/pull/\d+", false)
+            .unwrap();
+        watchers.add("prompt".into(), r"proceed\?", true).unwrap();
+
+        assert!(watchers
+            .feed(b"Created https://github.com/o/r/pull/4")
+            .is_empty());
+        let found = watchers.feed(b"2\nDo you want to proceed?");
+        assert_eq!(found[0].text, "https://github.com/o/r/pull/42");
+        assert_eq!(found.len(), 1);
+
+        // Output arrived during this period, so nothing is settled yet
+        assert!(watchers.settle().is_empty());
+        let found = watchers.settle();
+        assert_eq!(found[0].watcher_id, "prompt");
+        assert!(watchers.settle().is_empty());
+
+        // A once watcher is gone after its match
+        assert!(watchers.feed(b" proceed? x").is_empty());
+        assert!(!watchers.remove("prompt"));
+    }
+
+    #[test]
+    fn test_rejects_bad_patterns() {
+        let mut watchers = Watchers::default();
+        assert!(watchers.add("a".into(), "(", false).is_err());
+        assert!(watchers.add("b".into(), "x*", false).is_err());
+    }
+}
@@ -5,19 +5,389 @@
  * Replaces WebSocket-based terminal communication with direct IPC calls.
  */
 
-import { invoke } from "@tauri-apps/api/core";
+import { Channel, invoke } from "@tauri-apps/api/core";
 import { listen, UnlistenFn } from "@tauri-apps/api/event";
 
 /** Terminal output event payload from Rust */
 export interface TerminalOutputEvent {
   sessionId: string;
   data: number[]; // Raw bytes as array (Uint8Array in JS)
+  seq: number; // Stream offset of the first byte in data
+}
+
+/** Scrollback snapshot returned when reattaching to a session */
+export interface TerminalAttachResult {
+  sessionId: string;
+  data: number[];
+  seq: number; // Stream offset of the next byte; drop output events below it
 }
 
 /** Terminal exit event payload from Rust */
 export interface TerminalExitEvent {
   sessionId: string;
+  exitCode: number | null; // Null when terminated by a signal
+  signal: number | null; // Terminating signal number, if any
+  limit: "cpuTime" | "memory" | null; // Resource limit the process exceeded
+}
+
+/** Working directory change event payload from Rust */
+export interface TerminalCwdChangedEvent {
+  sessionId: string;
+  cwd: string;
+}
+
+/** What a Claude Code agent in a terminal session is doing */
+export type AgentState =
+  | "idle" // No agent has shown up in the session
+  | "thinking"
+  | "awaitingApproval" // A tool call waits for the user's permission
+  | "awaitingInput" // The agent waits for the next prompt
+  | "finished"; // The agent exited
+
+/** Agent state change event payload from Rust */
+export interface TerminalAgentStateEvent {
+  sessionId: string;
+  state: AgentState;
+}
+
+/** Idle event payload from Rust, emitted once per quiet period */
+export interface TerminalIdleEvent {
+  sessionId: string;
+  idleMs: number; // How long the session has printed nothing
+  lastOutputAt: number; // Unix ms
+}
+
+/** Event payload from Rust announcing a session is closed by a timeout */
+export interface TerminalTimeoutEvent {
+  sessionId: string;
+  reason: "idle" | "lifetime";
+}
+
+/** When a session is announced idle or closed; all off unless set */
+export interface TerminalTimeouts {
+  idleEventMs?: number | null; // Emit terminal-idle after this long without output
+  closeAfterIdleMs?: number | null; // Close after this long without input or output
+  maxLifetimeMs?: number | null; // Close this long after the session started
+}
+
+/** Resources used by a session's process tree (Linux only) */
+export interface TerminalStats {
+  sampledAt: number; // Unix ms
+  processes: number;
+  cpuPercent: number; // Since the previous sample; 100 is one full core
+  rssBytes: number;
+  threads: number;
+  openFds: number;
+}
+
+/** Periodic resource usage event payload from Rust */
+export interface TerminalStatsEvent {
+  sessionId: string;
+  stats: TerminalStats;
+}
+
+/** Usage above which a session is warned about; all off unless set */
+export interface TerminalStatsLimits {
+  cpuPercent?: number | null;
+  rssBytes?: number | null;
+  threads?: number | null;
+  openFds?: number | null;
+}
+
+/** Event payload from Rust announcing a session crossed a usage limit */
+export interface TerminalStatsWarningEvent {
+  sessionId: string;
+  resource: "cpuPercent" | "rssBytes" | "threads" | "openFds";
+  value: number;
+  limit: number;
+}
+
+/**
+ * What a session's processes may consume; all off unless set (Linux only).
+ * Memory, CPU and process limits need a cgroup v2 hierarchy the app may
+ * manage; without one they are dropped.
+ */
+export interface TerminalResourceLimits {
+  addressSpaceBytes?: number | null; // Virtual memory of each process
+  cpuTimeSecs?: number | null; // CPU time of each process
+  maxProcesses?: number | null; // Processes running at once
+  memoryMaxBytes?: number | null; // Memory of the whole session
+  cpuMaxPercent?: number | null; // CPU of the whole session; 100 is one core
+}
+
+/**
+ * Confines a session's writes to a few directories (Linux only). Uses
+ * bubblewrap when installed, unprivileged user namespaces otherwise.
+ */
+export interface TerminalSandboxOptions {
+  writable?: string[]; // Writable directories; the working directory if empty
+  hidden?: string[]; // Directories replaced by an empty one
+  noNetwork?: boolean; // Cut the session off from the network
+}
+
+/** Output watcher match event payload from Rust */
+export interface TerminalMatchEvent {
+  sessionId: string;
+  watcherId: string;
+  text: string; // The whole match
+  groups: (string | null)[]; // Numbered capture groups, from group 1
+  named: Record<string, string>; // Named capture groups that matched
+}
+
+/** Process running in the foreground of a terminal session */
+export interface ForegroundProcess {
+  pid: number;
+  name: string; // Command name, e.g. "vim"
+  isLeader: boolean; // True when the session's own shell is in the foreground
+}
+
+/** Foreground process change event payload from Rust */
+export interface TerminalForegroundChangedEvent {
+  sessionId: string;
+  process: ForegroundProcess;
+}
+
+/** Size of the little-endian stream offset prefixed to channel frames */
+const FRAME_HEADER_BYTES = 8;
+
+/**
+ * Create a channel that receives a session's output as binary frames.
+ *
+ * Frames skip JSON encoding and are delivered only to this window, unlike
+ * `terminal-output` events, which are broadcast to every window.
+ *
+ * @param callback - Called with the stream offset and bytes of each chunk
+ */
+export function createOutputChannel(
+  callback: (seq: number, data: Uint8Array) => void,
+): Channel<ArrayBuffer> {
+  const channel = new Channel<ArrayBuffer>();
+  channel.onmessage = (frame) => {
+    const view = new DataView(frame);
+    const seq = Number(view.getBigUint64(0, true));
+    callback(seq, new Uint8Array(frame, FRAME_HEADER_BYTES));
+  };
+  return channel;
+}
+
+/** Color of a snapshot cell: palette index or [r, g, b] */
+export type TerminalCellColor = number | [number, number, number];
+
+/** A styled cell of a terminal snapshot */
+export interface TerminalCell {
+  text: string;
+  fg?: TerminalCellColor;
+  bg?: TerminalCellColor;
+  bold: boolean;
+  italic: boolean;
+  underline: boolean;
+  inverse: boolean;
+  wide: boolean;
+}
+
+/** Visible screen of a terminal session as tracked by the backend */
+export interface TerminalSnapshot {
+  sessionId: string;
+  rows: number;
+  cols: number;
+  cursor: { row: number; col: number; visible: boolean };
+  title: string;
+  alternateScreen: boolean;
+  lines: string[];
+  cells?: TerminalCell[][]; // Only present when requested
+}
+
+/** Metadata describing a terminal session */
+export interface TerminalSessionInfo {
+  sessionId: string;
+  pid: number | null;
+  command: string[];
+  cwd: string | null;
+  createdAt: number; // Unix ms
+  lastActivityAt: number; // Unix ms of the last input or output
+  lastInputAt: number; // Unix ms
+  lastOutputAt: number; // Unix ms
+  rows: number;
+  cols: number;
+  title: string;
+  persistent: boolean;
+  running: boolean;
   exitCode: number | null;
+  signal: number | null;
+  labels: Record<string, string>;
+  foreground: ForegroundProcess | null;
+  agentState: AgentState;
+  redactions: Record<string, number>; // Secrets masked in the output, by rule
+  timeouts: TerminalTimeouts;
+  statsLimits: TerminalStatsLimits;
+  resourceLimits: TerminalResourceLimits; // Limits in force
+}
+
+/** A command run in a session with shell integration */
+export interface TerminalCommandRecord {
+  command: string;
+  cwd: string | null;
+  startedAt: number; // Unix ms
+  finishedAt: number | null; // Null while the command runs
+  exitCode: number | null; // Null while running or when not reported
+  outputStart: number; // Stream offset of the first output byte
+  outputEnd: number | null; // Stream offset just past the output
+  output: string; // Plain text, escape sequences removed
+  outputTruncated: boolean; // Only the tail of long output is kept
+}
+
+/** A step of a terminal automation script */
+export type TerminalScriptStep =
+  | { type: "send"; text: string } // e.g. "claude\r"
+  | { type: "expect"; pattern: string; timeoutMs?: number } // Rust regex syntax
+  | { type: "waitForIdle"; quietMs?: number; timeoutMs?: number }
+  | { type: "waitForExit"; timeoutMs?: number };
+
+/** What a script step did */
+export interface TerminalScriptStepResult {
+  index: number;
+  step: TerminalScriptStep;
+  ok: boolean;
+  startedMs: number; // Since the script started
+  durationMs: number;
+  matched: string | null; // Text matched by expect
+  groups: (string | null)[]; // Capture groups of the expect match
+  exitCode: number | null; // From waitForExit
+  signal: number | null;
+  error: string | null;
+}
+
+/** Outcome of a terminal automation script */
+export interface TerminalScriptTranscript {
+  success: boolean;
+  failedStep: number | null; // Later steps did not run
+  durationMs: number;
+  steps: TerminalScriptStepResult[];
+}
+
+/** Outcome of writing to one member of a session group */
+export interface TerminalGroupWriteResult {
+  sessionId: string;
+  ok: boolean;
+  error: string | null;
+}
+
+/** An asciicast v2 recording of a terminal session */
+export interface TerminalRecording {
+  path: string;
+  title: string | null;
+  timestamp: number; // Unix seconds when recording started
+  width: number;
+  height: number;
+  sizeBytes: number;
+}
+
+/** Output batching and flow control for a terminal session */
+export interface TerminalOutputOptions {
+  flushIntervalMs?: number; // Max delay before a batch is sent (default 8)
+  maxBatchBytes?: number; // Batch size that is sent immediately (default 64 KiB)
+  flowControlWindow?: number; // Pause after this many unacknowledged bytes
+  holdIncompleteUtf8?: boolean; // Never split a multi-byte character across chunks
+}
+
+/** Program to run in a terminal instead of the login shell */
+export interface TerminalCommand {
+  command: string;
+  args?: string[];
+  login?: boolean; // Run through a login shell (sources profile files)
+  interactive?: boolean; // Run through an interactive shell (sources rc files)
+}
+
+/** A named shell configuration stored in the app config */
+export interface ShellProfile {
+  name: string;
+  program: string; // e.g. "/usr/bin/fish" or "nu"
+  args?: string[]; // Passed instead of "-l -i"
+  env?: Record<string, string>; // Overridden by env given at spawn time
+  initialCommand?: string | null; // Typed once the shell shows its first prompt
+  term?: string | null; // Defaults to "xterm-256color"
+}
+
+/**
+ * Which of the app's environment variables sessions inherit. Names may end
+ * in "*" to match a prefix, e.g. "AWS_*".
+ */
+export interface TerminalEnvPolicy {
+  allow?: string[] | null; // Only these are inherited; all when unset
+  deny?: string[]; // Never inherited, even when allowed
+}
+
+/** How the environment of a session was built (names only, no values) */
+export interface TerminalEnvAudit {
+  removed: string[]; // App variables withheld by the policy
+  set: string[]; // Variables from the profile, env and TERM
+  injected: { variable: string; secret: string }[];
+}
+
+/**
+ * Which secrets are masked in a session's output. The values of injected
+ * secrets are masked unless redaction is disabled.
+ */
+export interface TerminalRedactionOptions {
+  disabled?: boolean;
+  /**
+   * Also mask the built-in token formats (GitHub, AWS, ...); output that
+   * could start a token, like an echoed "g", is held back until the next
+   * output rules it out
+   */
+  builtin?: boolean;
+  patterns?: string[]; // Additional regexes to mask
+}
+
+/** Options of spawnTerminal beyond the working directory and environment */
+export interface TerminalSpawnOptions {
+  scrollbackBytes?: number; // Size of the replay buffer kept for reattaching
+  command?: TerminalCommand; // Program to run instead of the login shell
+  /**
+   * Channel from createOutputChannel; output events are broadcast instead
+   * when omitted
+   */
+  onOutput?: Channel<ArrayBuffer>;
+  /**
+   * Batching and flow control; with a flowControlWindow the consumer must
+   * call ackTerminalOutput as it renders output
+   */
+  output?: TerminalOutputOptions;
+  /**
+   * Host the session in the background session daemon so it keeps running
+   * when the app quits (macOS/Linux only)
+   */
+  persistent?: boolean;
+  labels?: Record<string, string>; // Tags for finding the session later, e.g. { task: "TASK-42" }
+  /**
+   * Start bash or zsh with prompt marks so commands can be listed with
+   * getTerminalCommandHistory (macOS/Linux only)
+   */
+  shellIntegration?: boolean;
+  /**
+   * Name of a shell profile to start instead of the login shell; cannot be
+   * combined with command, nor a bash or zsh profile with args with
+   * shellIntegration
+   */
+  profile?: string;
+  /**
+   * Environment variables to set from the secret store, mapped to secret
+   * names, e.g. { SPECFLUX_API_KEY: "specflux" }
+   */
+  secrets?: Record<string, string>;
+  redaction?: TerminalRedactionOptions; // Which secrets are masked in the output
+  timeouts?: TerminalTimeouts; // When the session is announced idle or closed
+  statsLimits?: TerminalStatsLimits; // Usage that triggers a terminal-stats-warning event
+  /**
+   * What the session's processes may consume; the exit event names the
+   * limit a session was killed for exceeding
+   */
+  resourceLimits?: TerminalResourceLimits;
+  /**
+   * Make everything but a few directories read-only; cannot be combined
+   * with shellIntegration
+   */
+  sandbox?: TerminalSandboxOptions;
 }
 
 /**
@@ -26,19 +396,164 @@ export interface TerminalExitEvent {
  * @param sessionId - Unique identifier for the session (e.g., "task-123")
  * @param cwd - Working directory for the shell
  * @param env - Additional environment variables
+ * @param options - Command, output delivery and session policies
  */
 export async function spawnTerminal(
   sessionId: string,
   cwd?: string,
   env?: Record<string, string>,
+  options: TerminalSpawnOptions = {},
 ): Promise<void> {
+  const { command, ...rest } = options;
   await invoke("spawn_terminal", {
     sessionId,
-    cwd: cwd ?? null,
-    env: env ?? null,
+    options: {
+      ...rest,
+      cwd,
+      env,
+      command: command?.command,
+      args: command?.args,
+      login: command?.login,
+      interactive: command?.interactive,
+    },
   });
 }
 
+/**
+ * Get the policy deciding which app environment variables sessions inherit.
+ */
+export async function getTerminalEnvPolicy(): Promise<TerminalEnvPolicy> {
+  return await invoke("get_terminal_env_policy");
+}
+
+/**
+ * Set the environment policy for sessions spawned from now on.
+ */
+export async function setTerminalEnvPolicy(
+  policy: TerminalEnvPolicy,
+): Promise<void> {
+  await invoke("set_terminal_env_policy", { policy });
+}
+
+/**
+ * List the names of the secrets sessions can reference.
+ */
+export async function listTerminalSecrets(): Promise<string[]> {
+  return await invoke("list_terminal_secrets");
+}
+
+/**
+ * Store a secret that sessions can reference by name through the secrets
+ * option of spawnTerminal. The value is entered in a native dialog and kept
+ * in the OS keychain, so it never passes through the webview.
+ *
+ * @returns false if the dialog was cancelled
+ */
+export async function setTerminalSecret(name: string): Promise<boolean> {
+  return await invoke("set_terminal_secret", { name });
+}
+
+/**
+ * Delete a secret.
+ *
+ * @returns false if there was no secret with this name
+ */
+export async function deleteTerminalSecret(name: string): Promise<boolean> {
+  return await invoke("delete_terminal_secret", { name });
+}
+
+/**
+ * Which variables were withheld, set and injected when a session spawned.
+ */
+export async function getTerminalEnvAudit(
+  sessionId: string,
+): Promise<TerminalEnvAudit> {
+  return await invoke("terminal_env_audit", { sessionId });
+}
+
+/**
+ * Change when a session is announced idle or closed.
+ */
+export async function setTerminalTimeouts(
+  sessionId: string,
+  timeouts: TerminalTimeouts,
+): Promise<void> {
+  await invoke("terminal_set_timeouts", { sessionId, timeouts });
+}
+
+/**
+ * Get the resource usage of a session's process tree.
+ *
+ * @returns null where usage cannot be measured (outside Linux) or once the
+ *   process is gone
+ */
+export async function getTerminalStats(
+  sessionId: string,
+): Promise<TerminalStats | null> {
+  return await invoke("terminal_stats", { sessionId });
+}
+
+/**
+ * Change the usage above which a session is warned about.
+ */
+export async function setTerminalStatsLimits(
+  sessionId: string,
+  limits: TerminalStatsLimits,
+): Promise<void> {
+  await invoke("terminal_set_stats_limits", { sessionId, limits });
+}
+
+/**
+ * List the shell profiles stored in the app config.
+ */
+export async function listShellProfiles(): Promise<ShellProfile[]> {
+  return await invoke("list_shell_profiles");
+}
+
+/**
+ * Replace the shell profiles stored in the app config.
+ *
+ * Names must be unique and every profile needs a program.
+ */
+export async function saveShellProfiles(
+  profiles: ShellProfile[],
+): Promise<void> {
+  await invoke("save_shell_profiles", { profiles });
+}
+
+/**
+ * Write raw bytes to a terminal session.
+ *
+ * Unlike writeToTerminal the data need not be valid UTF-8.
+ *
+ * @param sessionId - Session identifier
+ * @param data - Raw bytes, or a base64-encoded string
+ */
+export async function writeBytesToTerminal(
+  sessionId: string,
+  data: Uint8Array | string,
+): Promise<void> {
+  await invoke("terminal_write_bytes", {
+    sessionId,
+    data: typeof data === "string" ? data : Array.from(data),
+  });
+}
+
+/**
+ * Acknowledge terminal output that has been consumed.
+ *
+ * Only needed for sessions spawned with a flowControlWindow.
+ *
+ * @param sessionId - Session identifier
+ * @param seq - Stream offset just past the last consumed byte
+ */
+export async function ackTerminalOutput(
+  sessionId: string,
+  seq: number,
+): Promise<void> {
+  await invoke("terminal_ack", { sessionId, seq });
+}
+
 /**
  * Write input data to a terminal session.
  *
@@ -71,13 +586,282 @@ export async function resizeTerminal(
   });
 }
 
+/**
+ * Reattach to a running terminal session.
+ *
+ * Returns the buffered scrollback so the terminal can be redrawn. Output
+ * events with a `seq` lower than the returned `seq` are already included.
+ *
+ * @param sessionId - Session identifier
+ * @param onOutput - New output channel replacing the one the session had
+ */
+export async function attachTerminal(
+  sessionId: string,
+  onOutput?: Channel<ArrayBuffer>,
+): Promise<TerminalAttachResult> {
+  return await invoke("terminal_attach", {
+    sessionId,
+    onOutput: onOutput ?? null,
+  });
+}
+
+/**
+ * Capture what a terminal session currently shows, without a visible xterm.
+ *
+ * @param sessionId - Session identifier
+ * @param styled - Include per-cell colors and attributes
+ */
+export async function getTerminalSnapshot(
+  sessionId: string,
+  styled = false,
+): Promise<TerminalSnapshot> {
+  return await invoke("terminal_snapshot", { sessionId, styled });
+}
+
+/**
+ * Get the process running in the foreground of a terminal session.
+ *
+ * @returns Null until the session has been sampled once
+ */
+export async function getTerminalForegroundProcess(
+  sessionId: string,
+): Promise<ForegroundProcess | null> {
+  return await invoke("terminal_foreground_process", { sessionId });
+}
+
+/**
+ * Watch a terminal session's output for a pattern.
+ *
+ * Each match emits a `terminal-match` event (see onTerminalMatch). Matching
+ * runs on the output with escape sequences removed and may span chunks.
+ * A match that ends where the output stops is reported once the session
+ * has been quiet for about a second, so a URL is never cut short.
+ *
+ * @param sessionId - Session identifier
+ * @param watcherId - Identifier reported with matches; replaces an existing
+ *   watcher with the same ID
+ * @param pattern - Regex in Rust syntax, e.g. "Tests: (?P<failed>\\d+) failed"
+ * @param once - Remove the watcher after its first match
+ */
+export async function watchTerminal(
+  sessionId: string,
+  watcherId: string,
+  pattern: string,
+  once = false,
+): Promise<void> {
+  await invoke("terminal_watch", { sessionId, watcherId, pattern, once });
+}
+
+/**
+ * Remove an output watcher.
+ *
+ * @returns false if the watcher did not exist
+ */
+export async function unwatchTerminal(
+  sessionId: string,
+  watcherId: string,
+): Promise<boolean> {
+  return await invoke("terminal_unwatch", { sessionId, watcherId });
+}
+
+/**
+ * Run an expect-style automation script against a terminal session.
+ *
+ * Steps run in order until one fails; steps without a timeout give up
+ * after 30 seconds and waitForIdle waits for 2 seconds of quiet by default.
+ *
+ * @example
+ * await runTerminalScript("task-12", [
+ *   { type: "send", text: "claude\r" },
+ *   { type: "expect", pattern: "\\? for shortcuts" },
+ *   { type: "send", text: "/implement TASK-12\r" },
+ *   { type: "waitForIdle", quietMs: 5000, timeoutMs: 1_800_000 },
+ * ]);
+ */
+export async function runTerminalScript(
+  sessionId: string,
+  steps: TerminalScriptStep[],
+): Promise<TerminalScriptTranscript> {
+  return await invoke("terminal_run_script", { sessionId, steps });
+}
+
+/**
+ * List the commands run in a terminal session, oldest first.
+ *
+ * Requires a session spawned with shellIntegration, or a shell that emits
+ * OSC 133 marks itself.
+ *
+ * @param sessionId - Session identifier
+ * @param limit - Only return the most recent commands
+ */
+export async function getTerminalCommandHistory(
+  sessionId: string,
+  limit?: number,
+): Promise<TerminalCommandRecord[]> {
+  return await invoke("terminal_command_history", {
+    sessionId,
+    limit: limit ?? null,
+  });
+}
+
+/**
+ * Start recording a terminal session.
+ *
+ * Output, input and resizes are written as asciicast v2 to
+ * `<projectDir>/.specflux/recordings/`.
+ *
+ * @returns Path of the recording file
+ */
+export async function startTerminalRecording(
+  sessionId: string,
+  projectDir: string,
+): Promise<string> {
+  return await invoke("terminal_start_recording", { sessionId, projectDir });
+}
+
+/**
+ * Stop recording a terminal session.
+ *
+ * @returns Path of the finished recording, or null if none was running
+ */
+export async function stopTerminalRecording(
+  sessionId: string,
+): Promise<string | null> {
+  return await invoke("terminal_stop_recording", { sessionId });
+}
+
+/**
+ * List the terminal recordings of a project, newest first.
+ */
+export async function listTerminalRecordings(
+  projectDir: string,
+): Promise<TerminalRecording[]> {
+  return await invoke("list_terminal_recordings", { projectDir });
+}
+
 /**
  * Close a terminal session.
  *
+ * Terminates the shell and every process it started (SIGHUP, then SIGTERM,
+ * then SIGKILL) and resolves once they have all exited.
+ *
  * @param sessionId - Session identifier
+ * @param graceMs - Time each signal gets before escalating to the next
  */
-export async function closeTerminal(sessionId: string): Promise<void> {
-  await invoke("terminal_close", { sessionId });
+export async function closeTerminal(
+  sessionId: string,
+  graceMs?: number,
+): Promise<void> {
+  await invoke("terminal_close", { sessionId, graceMs: graceMs ?? null });
+}
+
+/**
+ * Create a group of terminal sessions that can receive the same input.
+ *
+ * @param groupId - Unique identifier for the group
+ * @param sessionIds - Initial members
+ */
+export async function createTerminalGroup(
+  groupId: string,
+  sessionIds: string[] = [],
+): Promise<void> {
+  await invoke("terminal_group_create", { groupId, sessionIds });
+}
+
+/**
+ * Add a terminal session to a group.
+ */
+export async function addToTerminalGroup(
+  groupId: string,
+  sessionId: string,
+): Promise<void> {
+  await invoke("terminal_group_add", { groupId, sessionId });
+}
+
+/**
+ * Remove a terminal session from a group.
+ *
+ * @returns false if the session was not a member
+ */
+export async function removeFromTerminalGroup(
+  groupId: string,
+  sessionId: string,
+): Promise<boolean> {
+  return await invoke("terminal_group_remove", { groupId, sessionId });
+}
+
+/**
+ * Delete a group, leaving its sessions running.
+ *
+ * @returns false if the group did not exist
+ */
+export async function deleteTerminalGroup(groupId: string): Promise<boolean> {
+  return await invoke("terminal_group_delete", { groupId });
+}
+
+/**
+ * List the sessions in a group.
+ */
+export async function getTerminalGroupMembers(
+  groupId: string,
+): Promise<string[]> {
+  return await invoke("terminal_group_members", { groupId });
+}
+
+/**
+ * Write input to every session in a group.
+ *
+ * Members that were closed or exited fail and are dropped from the group.
+ *
+ * @param groupId - Group identifier
+ * @param data - Input data to write (keyboard input)
+ * @returns A result per member
+ */
+export async function writeToTerminalGroup(
+  groupId: string,
+  data: string,
+): Promise<TerminalGroupWriteResult[]> {
+  return await invoke("terminal_write_group", { groupId, data });
+}
+
+/**
+ * Write raw bytes to every session in a group.
+ *
+ * Unlike writeToTerminalGroup the data need not be valid UTF-8.
+ *
+ * @param groupId - Group identifier
+ * @param data - Raw bytes, or a base64-encoded string
+ * @returns A result per member
+ */
+export async function writeBytesToTerminalGroup(
+  groupId: string,
+  data: Uint8Array | string,
+): Promise<TerminalGroupWriteResult[]> {
+  return await invoke("terminal_write_group_bytes", {
+    groupId,
+    data: typeof data === "string" ? data : Array.from(data),
+  });
+}
+
+/**
+ * Reconnect to persistent sessions that kept running while the app was
+ * closed. Call once on startup, then attach to the returned sessions.
+ *
+ * @returns IDs of the restored sessions
+ */
+export async function restoreTerminalSessions(): Promise<string[]> {
+  return await invoke("restore_terminal_sessions");
+}
+
+/**
+ * Describe terminal sessions.
+ *
+ * @param labels - Only return sessions carrying all of these labels
+ */
+export async function getTerminalSessions(
+  labels?: Record<string, string>,
+): Promise<TerminalSessionInfo[]> {
+  return await invoke("get_terminal_sessions", { labels: labels ?? null });
 }
 
 /**
@@ -126,3 +910,134 @@ export async function onTerminalExit(
     callback(event.payload);
   });
 }
+
+/**
+ * Listen for working directory changes of terminal sessions.
+ *
+ * Reported by shells that emit OSC 7, otherwise detected from the
+ * foreground process (Linux).
+ *
+ * @param callback - Function to call when a session changes directory
+ * @returns Unlisten function to stop listening
+ */
+export async function onTerminalCwdChanged(
+  callback: (event: TerminalCwdChangedEvent) => void,
+): Promise<UnlistenFn> {
+  return await listen<TerminalCwdChangedEvent>(
+    "terminal-cwd-changed",
+    (event) => {
+      callback(event.payload);
+    },
+  );
+}
+
+/**
+ * Listen for changes of the foreground process of terminal sessions,
+ * e.g. when the shell starts vim or returns to the prompt.
+ *
+ * @param callback - Function to call when the foreground process changes
+ * @returns Unlisten function to stop listening
+ */
+export async function onTerminalForegroundChanged(
+  callback: (event: TerminalForegroundChangedEvent) => void,
+): Promise<UnlistenFn> {
+  return await listen<TerminalForegroundChangedEvent>(
+    "terminal-foreground-changed",
+    (event) => {
+      callback(event.payload);
+    },
+  );
+}
+
+/**
+ * Listen for output watcher matches.
+ *
+ * @param callback - Function to call for each match
+ * @returns Unlisten function to stop listening
+ */
+export async function onTerminalMatch(
+  callback: (event: TerminalMatchEvent) => void,
+): Promise<UnlistenFn> {
+  return await listen<TerminalMatchEvent>("terminal-match", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for changes of the agent state of terminal sessions, e.g. to flag
+ * tasks whose agent waits for approval or input.
+ *
+ * @param callback - Function to call when a session's agent state changes
+ * @returns Unlisten function to stop listening
+ */
+export async function onTerminalAgentState(
+  callback: (event: TerminalAgentStateEvent) => void,
+): Promise<UnlistenFn> {
+  return await listen<TerminalAgentStateEvent>(
+    "terminal-agent-state",
+    (event) => {
+      callback(event.payload);
+    },
+  );
+}
+
+/**
+ * Listen for sessions that went quiet for their idle threshold.
+ *
+ * @param callback - Function to call when a session goes idle
+ * @returns Unlisten function to stop listening
+ */
+export async function onTerminalIdle(
+  callback: (event: TerminalIdleEvent) => void,
+): Promise<UnlistenFn> {
+  return await listen<TerminalIdleEvent>("terminal-idle", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for sessions closed because they were idle or ran too long. A
+ * terminal-exit event follows once the process is gone.
+ *
+ * @param callback - Function to call when a session times out
+ * @returns Unlisten function to stop listening
+ */
+export async function onTerminalTimeout(
+  callback: (event: TerminalTimeoutEvent) => void,
+): Promise<UnlistenFn> {
+  return await listen<TerminalTimeoutEvent>("terminal-timeout", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for the resource usage of sessions, sampled every two seconds.
+ *
+ * @param callback - Function to call with each sample
+ * @returns Unlisten function to stop listening
+ */
+export async function onTerminalStats(
+  callback: (event: TerminalStatsEvent) => void,
+): Promise<UnlistenFn> {
+  return await listen<TerminalStatsEvent>("terminal-stats", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for sessions crossing one of their usage limits. Emitted once per
+ * crossing; the session has to drop below the limit to warn again.
+ *
+ * @param callback - Function to call when a limit is crossed
+ * @returns Unlisten function to stop listening
+ */
+export async function onTerminalStatsWarning(
+  callback: (event: TerminalStatsWarningEvent) => void,
+): Promise<UnlistenFn> {
+  return await listen<TerminalStatsWarningEvent>(
+    "terminal-stats-warning",
+    (event) => {
+      callback(event.payload);
+    },
+  );
+}